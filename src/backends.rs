@@ -2,18 +2,25 @@ use std::collections::HashMap;
 
 use lazy_static::lazy_static;
 use serde::{Deserialize, Serialize};
+use structopt::StructOpt;
 
 use crate::types::PasteClient;
 
 pub mod dpaste;
 pub mod dpaste_com;
 pub mod fiche;
+pub mod gist;
+pub mod gitlab_snippet;
 pub mod haste;
 pub mod ix;
 pub mod modern_paste;
+pub mod null_pointer;
 pub mod onetimesecret;
 pub mod paste_rs;
+pub mod pastebin_com;
 pub mod pipfi;
+pub mod privatebin;
+pub mod rustypaste;
 pub mod sprunge;
 pub mod ubuntu;
 pub mod vpaste;
@@ -24,17 +31,145 @@ lazy_static! {
         m.insert(dpaste::NAME, dpaste::INFO);
         m.insert(dpaste_com::NAME, dpaste_com::INFO);
         m.insert(fiche::NAME, fiche::INFO);
+        m.insert(gist::NAME, gist::INFO);
+        m.insert(gitlab_snippet::NAME, gitlab_snippet::INFO);
         m.insert(haste::NAME, haste::INFO);
         m.insert(ix::NAME, ix::INFO);
         m.insert(modern_paste::NAME, modern_paste::INFO);
+        m.insert(null_pointer::NAME, null_pointer::INFO);
         m.insert(onetimesecret::NAME, onetimesecret::INFO);
         m.insert(paste_rs::NAME, paste_rs::INFO);
+        m.insert(pastebin_com::NAME, pastebin_com::INFO);
         m.insert(pipfi::NAME, pipfi::INFO);
+        m.insert(privatebin::NAME, privatebin::INFO);
+        m.insert(rustypaste::NAME, rustypaste::INFO);
         m.insert(sprunge::NAME, sprunge::INFO);
         m.insert(ubuntu::NAME, ubuntu::INFO);
         m.insert(vpaste::NAME, vpaste::INFO);
         m
     };
+    pub static ref BACKENDS_CAPABILITIES: HashMap<&'static str, Capabilities> = {
+        let mut m = HashMap::new();
+        m.insert(dpaste::NAME, dpaste::CAPABILITIES);
+        m.insert(dpaste_com::NAME, dpaste_com::CAPABILITIES);
+        m.insert(fiche::NAME, fiche::CAPABILITIES);
+        m.insert(gist::NAME, gist::CAPABILITIES);
+        m.insert(gitlab_snippet::NAME, gitlab_snippet::CAPABILITIES);
+        m.insert(haste::NAME, haste::CAPABILITIES);
+        m.insert(ix::NAME, ix::CAPABILITIES);
+        m.insert(modern_paste::NAME, modern_paste::CAPABILITIES);
+        m.insert(null_pointer::NAME, null_pointer::CAPABILITIES);
+        m.insert(onetimesecret::NAME, onetimesecret::CAPABILITIES);
+        m.insert(paste_rs::NAME, paste_rs::CAPABILITIES);
+        m.insert(pastebin_com::NAME, pastebin_com::CAPABILITIES);
+        m.insert(pipfi::NAME, pipfi::CAPABILITIES);
+        m.insert(privatebin::NAME, privatebin::CAPABILITIES);
+        m.insert(rustypaste::NAME, rustypaste::CAPABILITIES);
+        m.insert(sprunge::NAME, sprunge::CAPABILITIES);
+        m.insert(ubuntu::NAME, ubuntu::CAPABILITIES);
+        m.insert(vpaste::NAME, vpaste::CAPABILITIES);
+        m
+    };
+    /// Lexer names for each canonical language, for backends that take a syntax.
+    pub static ref BACKENDS_SYNTAXES: HashMap<&'static str, &'static [(&'static str, &'static str)]> = {
+        let mut m = HashMap::new();
+        m.insert(dpaste::NAME, dpaste::SYNTAXES);
+        m.insert(dpaste_com::NAME, dpaste_com::SYNTAXES);
+        m.insert(gitlab_snippet::NAME, gitlab_snippet::SYNTAXES);
+        m.insert(ix::NAME, ix::SYNTAXES);
+        m.insert(modern_paste::NAME, modern_paste::SYNTAXES);
+        m.insert(pastebin_com::NAME, pastebin_com::SYNTAXES);
+        m.insert(sprunge::NAME, sprunge::SYNTAXES);
+        m.insert(ubuntu::NAME, ubuntu::SYNTAXES);
+        m
+    };
+}
+
+/// What a backend can do besides pasting, declared by each backend next to its `NAME` and `INFO`.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct Capabilities {
+    pub title: bool,
+    pub expiry: ExpiryKind,
+    pub password: bool,
+    /// Pasting as a user, with a username or api key.
+    pub auth: bool,
+    /// Setting the name of the poster.
+    pub author: bool,
+    pub syntax: SyntaxKind,
+    pub delete: bool,
+    /// Getting the content of a paste back with `pc get`.
+    pub fetch: bool,
+    /// Pasting several files together as one paste.
+    pub multi_file: bool,
+    /// Largest paste the server accepts in bytes, where known.
+    pub max_size: Option<u64>,
+}
+
+/// How a backend lets the paste lifetime be set.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ExpiryKind {
+    None,
+    /// Any duration.
+    Duration,
+    /// One of a fixed set of lifetimes.
+    Choice,
+}
+
+/// How a backend gets the syntax to highlight a paste with.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SyntaxKind {
+    None,
+    /// Set by pc, from the config or server args.
+    Given,
+    /// Detected by the server from the content.
+    Detected,
+}
+
+impl Capabilities {
+    /// Supports nothing besides pasting; the base for each backend's `CAPABILITIES`.
+    pub const NONE: Capabilities = Capabilities {
+        title: false,
+        expiry: ExpiryKind::None,
+        password: false,
+        auth: false,
+        author: false,
+        syntax: SyntaxKind::None,
+        delete: false,
+        fetch: false,
+        multi_file: false,
+        max_size: None,
+    };
+
+    /// Names of the capabilities that can be checked with `supports`.
+    pub const NAMES: [&'static str; 9] = [
+        "title",
+        "expiry",
+        "password",
+        "auth",
+        "author",
+        "syntax",
+        "delete",
+        "fetch",
+        "multi_file",
+    ];
+
+    /// Whether the capability called `name` is supported, or None if there is no such capability.
+    pub fn supports(&self, name: &str) -> Option<bool> {
+        match name {
+            "title" => Some(self.title),
+            "expiry" => Some(self.expiry != ExpiryKind::None),
+            "password" => Some(self.password),
+            "auth" => Some(self.auth),
+            "author" => Some(self.author),
+            "syntax" => Some(self.syntax != SyntaxKind::None),
+            "delete" => Some(self.delete),
+            "fetch" => Some(self.fetch),
+            "multi_file" => Some(self.multi_file),
+            _ => None,
+        }
+    }
 }
 
 #[derive(Debug, Deserialize, Serialize, Clone)]
@@ -45,32 +180,335 @@ pub enum BackendConfig {
     Dpaste(dpaste::Backend),
     DpasteCom(dpaste_com::Backend),
     Fiche(fiche::Backend),
+    Gist(gist::Backend),
+    GitlabSnippet(gitlab_snippet::Backend),
     Haste(haste::Backend),
     Ix(ix::Backend),
     ModernPaste(modern_paste::Backend),
+    NullPointer(null_pointer::Backend),
     Onetimesecret(onetimesecret::Backend),
     PasteRs(paste_rs::Backend),
+    PastebinCom(pastebin_com::Backend),
     Pipfi(pipfi::Backend),
+    Privatebin(privatebin::Backend),
+    Rustypaste(rustypaste::Backend),
     Sprunge(sprunge::Backend),
     Ubuntu(ubuntu::Backend),
     Vpaste(vpaste::Backend),
 }
 
 impl BackendConfig {
+    /// Name of the backend, as used for `backend` in the config.
+    pub fn name(&self) -> &'static str {
+        match self {
+            BackendConfig::Dpaste(_) => dpaste::NAME,
+            BackendConfig::DpasteCom(_) => dpaste_com::NAME,
+            BackendConfig::Fiche(_) => fiche::NAME,
+            BackendConfig::Gist(_) => gist::NAME,
+            BackendConfig::GitlabSnippet(_) => gitlab_snippet::NAME,
+            BackendConfig::Haste(_) => haste::NAME,
+            BackendConfig::Ix(_) => ix::NAME,
+            BackendConfig::ModernPaste(_) => modern_paste::NAME,
+            BackendConfig::NullPointer(_) => null_pointer::NAME,
+            BackendConfig::Onetimesecret(_) => onetimesecret::NAME,
+            BackendConfig::PasteRs(_) => paste_rs::NAME,
+            BackendConfig::PastebinCom(_) => pastebin_com::NAME,
+            BackendConfig::Pipfi(_) => pipfi::NAME,
+            BackendConfig::Privatebin(_) => privatebin::NAME,
+            BackendConfig::Rustypaste(_) => rustypaste::NAME,
+            BackendConfig::Sprunge(_) => sprunge::NAME,
+            BackendConfig::Ubuntu(_) => ubuntu::NAME,
+            BackendConfig::Vpaste(_) => vpaste::NAME,
+        }
+    }
+
+    /// Where the server is, as shown by `pc list`: its url, or host and port for fiche.
+    pub fn url(&self) -> String {
+        match self {
+            BackendConfig::Dpaste(backend) => backend.url.to_string(),
+            BackendConfig::DpasteCom(backend) => backend.url.to_string(),
+            BackendConfig::Fiche(backend) => format!("{}:{}", backend.domain, backend.port),
+            BackendConfig::Gist(backend) => backend.url.to_string(),
+            BackendConfig::GitlabSnippet(backend) => backend.url.to_string(),
+            BackendConfig::Haste(backend) => backend.url.to_string(),
+            BackendConfig::Ix(backend) => backend.url.to_string(),
+            BackendConfig::ModernPaste(backend) => backend.url.to_string(),
+            BackendConfig::NullPointer(backend) => backend.url.to_string(),
+            BackendConfig::Onetimesecret(backend) => backend.url.to_string(),
+            BackendConfig::PasteRs(backend) => backend.url.to_string(),
+            BackendConfig::PastebinCom(backend) => backend.url.to_string(),
+            BackendConfig::Pipfi(backend) => backend.url.to_string(),
+            BackendConfig::Privatebin(backend) => backend.url.to_string(),
+            BackendConfig::Rustypaste(backend) => backend.url.to_string(),
+            BackendConfig::Sprunge(backend) => backend.url.to_string(),
+            BackendConfig::Ubuntu(backend) => backend.url.to_string(),
+            BackendConfig::Vpaste(backend) => backend.url.to_string(),
+        }
+    }
+
+    pub fn capabilities(&self) -> Capabilities {
+        BACKENDS_CAPABILITIES[self.name()]
+    }
+
     pub fn extract_backend(self) -> Box<dyn PasteClient> {
         match self {
             BackendConfig::Dpaste(backend) => Box::new(backend),
             BackendConfig::DpasteCom(backend) => Box::new(backend),
             BackendConfig::Fiche(backend) => Box::new(backend),
+            BackendConfig::Gist(backend) => Box::new(backend),
+            BackendConfig::GitlabSnippet(backend) => Box::new(backend),
             BackendConfig::Haste(backend) => Box::new(backend),
             BackendConfig::Ix(backend) => Box::new(backend),
             BackendConfig::ModernPaste(backend) => Box::new(backend),
+            BackendConfig::NullPointer(backend) => Box::new(backend),
             BackendConfig::Onetimesecret(backend) => Box::new(backend),
             BackendConfig::PasteRs(backend) => Box::new(backend),
+            BackendConfig::PastebinCom(backend) => Box::new(backend),
             BackendConfig::Pipfi(backend) => Box::new(backend),
+            BackendConfig::Privatebin(backend) => Box::new(backend),
+            BackendConfig::Rustypaste(backend) => Box::new(backend),
             BackendConfig::Sprunge(backend) => Box::new(backend),
             BackendConfig::Ubuntu(backend) => Box::new(backend),
             BackendConfig::Vpaste(backend) => Box::new(backend),
         }
     }
 }
+
+/// A server arg accepted by a backend.
+#[derive(Debug, Serialize)]
+pub struct OptionInfo {
+    pub name: String,
+    pub short: Option<char>,
+    pub long: Option<String>,
+    /// Name of the value, for options that take one.
+    pub value_name: Option<String>,
+    pub takes_value: bool,
+    pub help: Option<String>,
+}
+
+/// Help template listing the flags of a backend, then its options after `OPTIONS_HEADING`.
+const OPTIONS_TEMPLATE: &str = "{flags}\nOPTIONS\n{options}";
+const OPTIONS_HEADING: &str = "OPTIONS";
+
+/// The server args accepted by the backend called `name`.
+pub fn backend_options(name: &str) -> Option<Vec<OptionInfo>> {
+    let app = match name {
+        dpaste::NAME => dpaste::Opt::clap(),
+        dpaste_com::NAME => dpaste_com::Opt::clap(),
+        fiche::NAME => fiche::Opt::clap(),
+        gist::NAME => gist::Opt::clap(),
+        gitlab_snippet::NAME => gitlab_snippet::Opt::clap(),
+        haste::NAME => haste::Opt::clap(),
+        ix::NAME => ix::Opt::clap(),
+        modern_paste::NAME => modern_paste::Opt::clap(),
+        null_pointer::NAME => null_pointer::Opt::clap(),
+        onetimesecret::NAME => onetimesecret::Opt::clap(),
+        paste_rs::NAME => paste_rs::Opt::clap(),
+        pastebin_com::NAME => pastebin_com::Opt::clap(),
+        pipfi::NAME => pipfi::Opt::clap(),
+        privatebin::NAME => privatebin::Opt::clap(),
+        rustypaste::NAME => rustypaste::Opt::clap(),
+        sprunge::NAME => sprunge::Opt::clap(),
+        ubuntu::NAME => ubuntu::Opt::clap(),
+        vpaste::NAME => vpaste::Opt::clap(),
+        _ => return None,
+    };
+
+    // clap 2 has no public api for listing the args of an app, so they are read back from a help
+    // message listing just the flags and then the options, unwrapped so each is on one line. They
+    // come out in the order the help message lists them
+    let mut help = vec![];
+    app.template(OPTIONS_TEMPLATE)
+        .set_term_width(0)
+        .write_help(&mut help)
+        .expect("help can be written to a vec");
+    let help = String::from_utf8_lossy(&help);
+
+    let mut options: Vec<OptionInfo> = vec![];
+    let mut takes_value = false;
+    for line in help.lines() {
+        if line == OPTIONS_HEADING {
+            takes_value = true;
+            continue;
+        }
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+        // help messages running over several lines continue on lines of their own
+        if !line.starts_with('-') {
+            if let Some(help) = options.last_mut().and_then(|o| o.help.as_mut()) {
+                help.push(' ');
+                help.push_str(line);
+            }
+            continue;
+        }
+
+        let (spec, help) = match line.find("  ") {
+            Some(i) => (&line[..i], Some(line[i..].trim().to_owned())),
+            None => (line, None),
+        };
+        let mut option = OptionInfo {
+            name: String::new(),
+            short: None,
+            long: None,
+            value_name: None,
+            takes_value,
+            help,
+        };
+        for part in spec.split(&[',', ' '][..]).filter(|p| !p.is_empty()) {
+            if let Some(long) = part.strip_prefix("--") {
+                option.long = Some(long.to_owned());
+            } else if let Some(short) = part.strip_prefix('-') {
+                option.short = short.chars().next();
+            } else if part.starts_with('<') {
+                option.value_name = Some(
+                    part.trim_start_matches('<')
+                        .split('>')
+                        .next()
+                        .unwrap_or("")
+                        .to_owned(),
+                );
+            }
+        }
+        // clap's own --help and --version aren't server args
+        if option.long.as_deref() == Some("help") || option.long.as_deref() == Some("version") {
+            continue;
+        }
+        option.name = match (&option.long, option.short) {
+            (Some(long), _) => long.replace('-', "_"),
+            (None, Some(short)) => short.to_string(),
+            (None, None) => continue,
+        };
+        options.push(option);
+    }
+    Some(options)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn backend_options_finds_every_server_arg() {
+        let expected: HashMap<&str, &[&str]> = [
+            (dpaste::NAME, &["expires", "syntax", "url"][..]),
+            (
+                dpaste_com::NAME,
+                &["author", "expires", "syntax", "title", "url"][..],
+            ),
+            (fiche::NAME, &["domain", "port"][..]),
+            (
+                gist::NAME,
+                &[
+                    "public",
+                    "secret",
+                    "description",
+                    "filename",
+                    "token",
+                    "update",
+                    "url",
+                ][..],
+            ),
+            (
+                gitlab_snippet::NAME,
+                &[
+                    "filename",
+                    "project",
+                    "syntax",
+                    "title",
+                    "token",
+                    "url",
+                    "visibility",
+                ][..],
+            ),
+            (haste::NAME, &["url"][..]),
+            (ix::NAME, &["apikey", "syntax", "url", "username"][..]),
+            (
+                modern_paste::NAME,
+                &["apikey", "expires", "password", "syntax", "title", "url"][..],
+            ),
+            (
+                null_pointer::NAME,
+                &["no_secret", "secret", "expires", "filename", "url"][..],
+            ),
+            (
+                onetimesecret::NAME,
+                &[
+                    "apikey",
+                    "expires",
+                    "password",
+                    "recipient",
+                    "url",
+                    "username",
+                ][..],
+            ),
+            (paste_rs::NAME, &["url"][..]),
+            (
+                pastebin_com::NAME,
+                &[
+                    "api_dev_key",
+                    "api_user_key",
+                    "expires",
+                    "password",
+                    "privacy",
+                    "syntax",
+                    "title",
+                    "url",
+                    "username",
+                ][..],
+            ),
+            (pipfi::NAME, &["url"][..]),
+            (
+                privatebin::NAME,
+                &["burn", "discussion", "expires", "format", "password", "url"][..],
+            ),
+            (
+                rustypaste::NAME,
+                &[
+                    "oneshot",
+                    "remote",
+                    "shorten",
+                    "delete_token",
+                    "expires",
+                    "filename",
+                    "token",
+                    "url",
+                ][..],
+            ),
+            (sprunge::NAME, &["syntax", "url"][..]),
+            (ubuntu::NAME, &["author", "expires", "syntax", "url"][..]),
+            (vpaste::NAME, &["url"][..]),
+        ]
+        .iter()
+        .cloned()
+        .collect();
+
+        for name in BACKENDS_INFO.keys() {
+            let options = backend_options(name).expect("every backend has options");
+            let names: Vec<&str> = options.iter().map(|o| o.name.as_str()).collect();
+            assert_eq!(Some(&&names[..]), expected.get(name), "{}", name);
+            for option in &options {
+                assert!(option.help.is_some(), "{} {}", name, option.name);
+                assert_eq!(option.takes_value, option.value_name.is_some());
+            }
+        }
+        assert_eq!(BACKENDS_INFO.len(), expected.len());
+        assert!(backend_options("nope").is_none());
+    }
+
+    #[test]
+    fn backend_options_reads_value_names_and_flags() {
+        let options = backend_options(null_pointer::NAME).unwrap();
+        let no_secret = &options[0];
+        assert_eq!(no_secret.short, None);
+        assert_eq!(no_secret.long.as_deref(), Some("no-secret"));
+        assert!(!no_secret.takes_value);
+
+        let options = backend_options(fiche::NAME).unwrap();
+        let port = &options[1];
+        assert_eq!(port.short, Some('p'));
+        assert_eq!(port.value_name.as_deref(), Some("port"));
+        assert!(port.takes_value);
+    }
+}
@@ -1,17 +1,19 @@
 use std::fmt::{self, Display, Formatter};
-use std::time::Duration;
+use std::time::{Duration, SystemTime};
 
-use reqwest::multipart::Form;
-use reqwest::Client;
+use reqwest::multipart::{Form, Part};
 use serde::{Deserialize, Serialize};
 use structopt::StructOpt;
 use url::Url;
 
+use crate::backends::{Capabilities, ExpiryKind, SyntaxKind};
+use crate::client::{ClientConfig, ResponseExt};
 use crate::error::PasteResult;
-use crate::types::PasteClient;
+use crate::syntax;
+use crate::types::{PasteClient, PasteData, PasteInfo};
 use crate::utils::{
-    override_if_present, override_option_duration_with_option_none,
-    override_option_with_option_none, serde_humantime, serde_url,
+    override_if_present, override_option_duration_with_option_none, override_syntax,
+    serde_humantime, serde_url,
 };
 
 #[derive(Debug, Deserialize, Serialize, Clone)]
@@ -62,22 +64,32 @@ Example config block:
     expires = "3600s"
 "#;
 
+pub const CAPABILITIES: Capabilities = Capabilities {
+    syntax: SyntaxKind::Given,
+    expiry: ExpiryKind::Duration,
+    ..Capabilities::NONE
+};
+
+pub const SYNTAXES: &[(&str, &str)] = syntax::PYGMENTS;
+
 impl PasteClient for Backend {
     fn apply_args(&mut self, args: Vec<String>) -> clap::Result<()> {
         let opt = Opt::from_iter_safe(args)?;
         override_if_present(&mut self.url, opt.url);
-        override_option_with_option_none(&mut self.syntax, opt.syntax);
+        override_syntax(&mut self.syntax, opt.syntax);
         override_option_duration_with_option_none(&mut self.expires, opt.expires)?;
         Ok(())
     }
 
-    fn paste(&self, data: String) -> PasteResult<Url> {
+    fn paste(&self, client_config: &ClientConfig, data: PasteData) -> PasteResult<PasteInfo> {
         let mut api_endpoint: Url = self.url.clone();
         api_endpoint.set_path("/api/");
 
-        let form = Form::new().text("content", data).text("format", "url");
-        let form = match self.syntax {
-            Some(ref syntax) => form.text("lexer", syntax.to_owned()),
+        let form = Form::new()
+            .part("content", Part::bytes(data.into_bytes()?))
+            .text("format", "url");
+        let form = match syntax::to_send(&self.syntax) {
+            Some(syntax) => form.text("lexer", syntax.to_owned()),
             None => form,
         };
         let form = match self.expires {
@@ -85,10 +97,12 @@ impl PasteClient for Backend {
             None => form,
         };
 
-        let text = Client::new()
+        let text = client_config
+            .build()?
             .post(api_endpoint)
             .multipart(form)
             .send()?
+            .check_status()?
             .text()?;
 
         match Url::parse(&text) {
@@ -97,9 +111,38 @@ impl PasteClient for Backend {
                 e, text
             )
             .into()),
-            Ok(url) => Ok(url),
+            Ok(url) => {
+                let mut info = PasteInfo::from(url);
+                let mut raw_url = info.url.clone();
+                raw_url
+                    .path_segments_mut()
+                    .map_err(|_| format!("unexpected paste url: {}", info.url))?
+                    .pop_if_empty()
+                    .push("raw");
+                info.raw_url = Some(raw_url);
+                info.expires = self.expires.map(|expires| SystemTime::now() + expires);
+                Ok(info)
+            }
+        }
+    }
+
+    fn default_syntax(&mut self, syntax: &str) {
+        if self.syntax.is_none() {
+            self.syntax = Some(syntax.to_owned());
         }
     }
+
+    fn set_syntax(&mut self, syntax: Option<&str>) {
+        self.syntax = syntax.map(|s| s.to_owned());
+    }
+
+    fn syntax(&self) -> Option<&str> {
+        self.syntax.as_deref()
+    }
+
+    fn host(&self) -> Option<String> {
+        self.url.host_str().map(|h| h.to_owned())
+    }
 }
 
 impl Display for Backend {
@@ -1,17 +1,20 @@
 use std::fmt::{self, Display, Formatter};
 use std::time::Duration;
 
-use reqwest::multipart::Form;
-use reqwest::Client;
+use reqwest::header::EXPIRES;
+use reqwest::multipart::{Form, Part};
 use serde::{Deserialize, Serialize};
 use structopt::StructOpt;
 use url::Url;
 
+use crate::backends::{Capabilities, ExpiryKind, SyntaxKind};
+use crate::client::{ClientConfig, ResponseExt};
 use crate::error::PasteResult;
-use crate::types::PasteClient;
+use crate::syntax;
+use crate::types::{PasteClient, PasteData, PasteInfo};
 use crate::utils::{
     override_if_present, override_option_duration_with_option_none,
-    override_option_with_option_none, serde_humantime, serde_url,
+    override_option_with_option_none, override_syntax, parse_http_date, serde_humantime, serde_url,
 };
 
 #[derive(Debug, Deserialize, Serialize, Clone)]
@@ -77,25 +80,35 @@ Example config block:
     title = "my paste"
 "#;
 
+pub const CAPABILITIES: Capabilities = Capabilities {
+    title: true,
+    author: true,
+    syntax: SyntaxKind::Given,
+    expiry: ExpiryKind::Duration,
+    ..Capabilities::NONE
+};
+
+pub const SYNTAXES: &[(&str, &str)] = syntax::PYGMENTS;
+
 impl PasteClient for Backend {
     fn apply_args(&mut self, args: Vec<String>) -> clap::Result<()> {
         let opt = Opt::from_iter_safe(args)?;
         override_if_present(&mut self.url, opt.url);
-        override_option_with_option_none(&mut self.syntax, opt.syntax);
+        override_syntax(&mut self.syntax, opt.syntax);
         override_option_with_option_none(&mut self.author, opt.author);
         override_option_with_option_none(&mut self.title, opt.title);
         override_option_duration_with_option_none(&mut self.expires, opt.expires)?;
         Ok(())
     }
 
-    fn paste(&self, data: String) -> PasteResult<Url> {
+    fn paste(&self, client_config: &ClientConfig, data: PasteData) -> PasteResult<PasteInfo> {
         // http://dpaste.com/api/v2/
         let mut api_endpoint: Url = self.url.clone();
         api_endpoint.set_path("/api/v2/");
 
-        let form = Form::new().text("content", data);
-        let form = match self.syntax {
-            Some(ref syntax) => form.text("syntax", syntax.to_owned()),
+        let form = Form::new().part("content", Part::bytes(data.into_bytes()?));
+        let form = match syntax::to_send(&self.syntax) {
+            Some(syntax) => form.text("syntax", syntax.to_owned()),
             None => form,
         };
         let form = match self.title {
@@ -115,11 +128,18 @@ impl PasteClient for Backend {
             }
         };
 
-        let text = Client::new()
+        let mut res = client_config
+            .build()?
             .post(api_endpoint)
             .multipart(form)
             .send()?
-            .text()?;
+            .check_status()?;
+        let expires = res
+            .headers()
+            .get(EXPIRES)
+            .and_then(|v| v.to_str().ok())
+            .and_then(parse_http_date);
+        let text = res.text()?;
 
         match Url::parse(&text) {
             Err(e) => Err(format!(
@@ -127,9 +147,43 @@ impl PasteClient for Backend {
                 e, text
             )
             .into()),
-            Ok(url) => Ok(url),
+            Ok(url) => {
+                let raw_url = Url::parse(&format!("{}.txt", url.as_str().trim_end_matches('/')))?;
+                let mut info = PasteInfo::from(url);
+                info.raw_url = Some(raw_url);
+                info.expires = expires;
+                Ok(info)
+            }
+        }
+    }
+
+    fn default_title(&mut self, title: &str) {
+        if self.title.is_none() {
+            self.title = Some(title.to_owned());
+        }
+    }
+
+    fn default_syntax(&mut self, syntax: &str) {
+        if self.syntax.is_none() {
+            self.syntax = Some(syntax.to_owned());
         }
     }
+
+    fn title(&self) -> Option<&str> {
+        self.title.as_deref()
+    }
+
+    fn set_syntax(&mut self, syntax: Option<&str>) {
+        self.syntax = syntax.map(|s| s.to_owned());
+    }
+
+    fn syntax(&self) -> Option<&str> {
+        self.syntax.as_deref()
+    }
+
+    fn host(&self) -> Option<String> {
+        self.url.host_str().map(|h| h.to_owned())
+    }
 }
 
 impl Display for Backend {
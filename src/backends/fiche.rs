@@ -1,13 +1,14 @@
 use std::fmt::{self, Display, Formatter};
-use std::io::{Read, Write};
-use std::net::TcpStream;
+use std::io::{self, Read};
 
 use serde::{Deserialize, Serialize};
 use structopt::StructOpt;
 use url::Url;
 
+use crate::backends::Capabilities;
+use crate::client::ClientConfig;
 use crate::error::PasteResult;
-use crate::types::PasteClient;
+use crate::types::{PasteClient, PasteData, PasteInfo};
 use crate::utils::override_if_present;
 
 #[derive(Debug, Deserialize, Serialize, Clone)]
@@ -54,6 +55,12 @@ Example config block:
     port = 9999
 "#;
 
+pub const CAPABILITIES: Capabilities = Capabilities {
+    // fiche's default buffer size; servers can be started with a different one
+    max_size: Some(32768),
+    ..Capabilities::NONE
+};
+
 impl PasteClient for Backend {
     fn apply_args(&mut self, args: Vec<String>) -> clap::Result<()> {
         let opt = Opt::from_iter_safe(args)?;
@@ -62,17 +69,23 @@ impl PasteClient for Backend {
         Ok(())
     }
 
-    fn paste(&self, data: String) -> PasteResult<Url> {
-        let mut stream = TcpStream::connect(format!("{}:{}", self.domain, self.port))?;
+    fn paste(&self, client_config: &ClientConfig, data: PasteData) -> PasteResult<PasteInfo> {
+        let mut stream = client_config.connect_tcp(&self.domain, self.port)?;
 
-        stream.write_all(data.as_bytes())?;
+        io::copy(&mut data.into_reader(), &mut stream)?;
 
         let mut response = String::new();
         stream.read_to_string(&mut response)?;
 
         let sanitized_data = response.trim_matches(char::from(0)).trim_end();
         let url = Url::parse(sanitized_data)?;
-        Ok(url)
+        let mut info = PasteInfo::from(url);
+        info.raw_url = Some(info.url.clone());
+        Ok(info)
+    }
+
+    fn host(&self) -> Option<String> {
+        Some(self.domain.clone())
     }
 }
 
@@ -1,12 +1,13 @@
 use std::fmt::{self, Display, Formatter};
 
-use reqwest::Client;
 use serde::{Deserialize, Serialize};
 use structopt::StructOpt;
 use url::Url;
 
+use crate::backends::Capabilities;
+use crate::client::{ClientConfig, ResponseExt};
 use crate::error::PasteResult;
-use crate::types::PasteClient;
+use crate::types::{PasteClient, PasteData, PasteInfo};
 use crate::utils::{override_if_present, serde_url};
 
 #[derive(Debug, Deserialize, Serialize, Clone)]
@@ -39,6 +40,11 @@ Example config block:
     url = "https://hastebin.com/"
 "#;
 
+pub const CAPABILITIES: Capabilities = Capabilities {
+    fetch: true,
+    ..Capabilities::NONE
+};
+
 impl PasteClient for Backend {
     fn apply_args(&mut self, args: Vec<String>) -> clap::Result<()> {
         let opt = Opt::from_iter_safe(args)?;
@@ -46,16 +52,43 @@ impl PasteClient for Backend {
         Ok(())
     }
 
-    fn paste(&self, data: String) -> PasteResult<Url> {
-        let client = Client::new();
+    fn paste(&self, client_config: &ClientConfig, data: PasteData) -> PasteResult<PasteInfo> {
+        let client = client_config.build()?;
 
         let mut base_url = self.url.clone();
 
         base_url.set_path("documents");
-        let info: HastePasteResponse = client.post(base_url.clone()).body(data).send()?.json()?;
+        let info: HastePasteResponse = client
+            .post(base_url.clone())
+            .body(data.into_body())
+            .send()?
+            .check_status()?
+            .json()?;
 
+        let mut raw_url = base_url.clone();
+        raw_url.set_path(&format!("raw/{}", info.key));
         base_url.set_path(&info.key);
-        Ok(base_url)
+        let mut info = PasteInfo::from(base_url);
+        info.raw_url = Some(raw_url);
+        Ok(info)
+    }
+
+    fn host(&self) -> Option<String> {
+        self.url.host_str().map(|h| h.to_owned())
+    }
+
+    fn fetch(&self, client_config: &ClientConfig, url: &Url) -> PasteResult<Vec<u8>> {
+        // paste urls are of the form /<key> or /<key>.<ext>; raw content lives at /raw/<key>
+        let key = match url.path_segments().and_then(|mut s| s.next()) {
+            Some(key) if !key.is_empty() => key.split('.').next().unwrap_or(key).to_owned(),
+            _ => return Err(format!("could not find paste key in url: {}", url).into()),
+        };
+
+        let mut raw_url = url.clone();
+        raw_url.set_path(&format!("raw/{}", key));
+        raw_url.set_query(None);
+
+        client_config.build()?.get(raw_url).send()?.read_body()
     }
 }
 
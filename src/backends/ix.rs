@@ -1,14 +1,18 @@
 use std::fmt::{self, Display, Formatter};
 
-use reqwest::multipart::Form;
-use reqwest::Client;
+use reqwest::multipart::{Form, Part};
 use serde::{Deserialize, Serialize};
 use structopt::StructOpt;
 use url::Url;
 
+use crate::backends::{Capabilities, SyntaxKind};
+use crate::client::{ClientConfig, ResponseExt};
 use crate::error::PasteResult;
-use crate::types::PasteClient;
-use crate::utils::{override_if_present, override_option_with_option_none, serde_url};
+use crate::syntax;
+use crate::types::{PasteClient, PasteData, PasteInfo};
+use crate::utils::{
+    override_if_present, override_option_with_option_none, override_syntax, serde_url,
+};
 
 #[derive(Debug, Deserialize, Serialize, Clone)]
 #[serde(deny_unknown_fields)]
@@ -75,11 +79,21 @@ Example config block:
     apikey = "hunter2"
 "#;
 
+pub const CAPABILITIES: Capabilities = Capabilities {
+    auth: true,
+    syntax: SyntaxKind::Given,
+    delete: true,
+    fetch: true,
+    ..Capabilities::NONE
+};
+
+pub const SYNTAXES: &[(&str, &str)] = syntax::PYGMENTS;
+
 impl PasteClient for Backend {
     fn apply_args(&mut self, args: Vec<String>) -> clap::Result<()> {
         let opt = Opt::from_iter_safe(args)?;
         override_if_present(&mut self.url, opt.url);
-        override_option_with_option_none(&mut self.syntax, opt.syntax);
+        override_syntax(&mut self.syntax, opt.syntax);
         override_option_with_option_none(&mut self.username, opt.username);
         override_option_with_option_none(&mut self.apikey, opt.apikey);
         // if let Some(new) = opt.reads {
@@ -97,15 +111,18 @@ impl PasteClient for Backend {
         Ok(())
     }
 
-    fn paste(&self, data: String) -> PasteResult<Url> {
-        let form = Form::new().text("f:1", data);
+    fn paste(&self, client_config: &ClientConfig, data: PasteData) -> PasteResult<PasteInfo> {
+        let form = Form::new().part("f:1", Part::bytes(data.into_bytes()?));
 
         // let form = match self.reads {
         //     Some(reads) => form.text("read:1", reads.to_string()),
         //     None => form,
         // };
 
-        let request = Client::new().post(self.url.clone()).multipart(form);
+        let request = client_config
+            .build()?
+            .post(self.url.clone())
+            .multipart(form);
 
         let request = match (&self.username, &self.apikey) {
             (None, None) => request,
@@ -119,19 +136,22 @@ impl PasteClient for Backend {
             }
         };
 
-        let text = request.send()?.error_for_status()?.text()?;
+        let text = request.send()?.check_status()?.error_for_status()?.text()?;
 
         // check initial url for errors before adding any extra params
-        if let Err(e) = Url::parse(&text) {
-            return Err(format!(
-                "could not parse response as url: {}\napi response body: {}",
-                e, text
-            )
-            .into());
-        }
+        let raw_url = match Url::parse(&text) {
+            Ok(url) => url,
+            Err(e) => {
+                return Err(format!(
+                    "could not parse response as url: {}\napi response body: {}",
+                    e, text
+                )
+                .into());
+            }
+        };
 
-        let text = match self.syntax {
-            Some(ref syntax) => format!("{}/{}", text, syntax),
+        let text = match syntax::to_send(&self.syntax) {
+            Some(syntax) => format!("{}/{}", text, syntax),
             None => text,
         };
 
@@ -143,9 +163,78 @@ impl PasteClient for Backend {
                 e, text
             )
             .into()),
-            Ok(url) => Ok(url),
+            Ok(url) => {
+                let mut info = PasteInfo::from(url);
+                info.raw_url = Some(raw_url);
+                Ok(info)
+            }
+        }
+    }
+
+    fn default_syntax(&mut self, syntax: &str) {
+        if self.syntax.is_none() {
+            self.syntax = Some(syntax.to_owned());
         }
     }
+
+    fn set_syntax(&mut self, syntax: Option<&str>) {
+        self.syntax = syntax.map(|s| s.to_owned());
+    }
+
+    fn syntax(&self) -> Option<&str> {
+        self.syntax.as_deref()
+    }
+
+    fn host(&self) -> Option<String> {
+        self.url.host_str().map(|h| h.to_owned())
+    }
+
+    fn fetch(&self, client_config: &ClientConfig, url: &Url) -> PasteResult<Vec<u8>> {
+        let mut raw_url = url.clone();
+        raw_url.set_path(&paste_id(url)?);
+
+        client_config.build()?.get(raw_url).send()?.read_body()
+    }
+
+    fn delete(
+        &self,
+        client_config: &ClientConfig,
+        url: &Url,
+        _token: Option<&str>,
+    ) -> PasteResult<()> {
+        // only pastes uploaded with a login can be deleted, using the same login
+        let (username, apikey) = match (&self.username, &self.apikey) {
+            (Some(ref username), Some(ref apikey)) => (username, apikey),
+            (_, _) => {
+                return Err(
+                    "Both username and apikey must be provided to delete a paste."
+                        .to_owned()
+                        .into(),
+                );
+            }
+        };
+
+        let mut raw_url = url.clone();
+        raw_url.set_path(&paste_id(url)?);
+
+        client_config
+            .build()?
+            .delete(raw_url)
+            .basic_auth(username, Some(apikey))
+            .send()?
+            .check_status()?
+            .error_for_status()?;
+        Ok(())
+    }
+}
+
+/// The paste id is the first path segment; anything after it (eg. the syntax added by paste) is a
+/// client-side option.
+fn paste_id(url: &Url) -> PasteResult<String> {
+    match url.path_segments().and_then(|mut s| s.next()) {
+        Some(id) if !id.is_empty() => Ok(id.to_owned()),
+        _ => Err(format!("could not find paste id in url: {}", url).into()),
+    }
 }
 
 impl Display for Backend {
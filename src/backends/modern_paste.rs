@@ -1,16 +1,18 @@
 use std::fmt::{self, Display, Formatter};
 use std::time::{Duration, SystemTime};
 
-use reqwest::Client;
 use serde::{Deserialize, Serialize};
 use structopt::StructOpt;
 use url::Url;
 
+use crate::backends::{Capabilities, ExpiryKind, SyntaxKind};
+use crate::client::{ClientConfig, ResponseExt};
 use crate::error::PasteResult;
-use crate::types::PasteClient;
+use crate::syntax;
+use crate::types::{PasteClient, PasteData, PasteInfo};
 use crate::utils::{
-    override_if_present, override_option_duration_with_option_none,
-    override_option_with_option_none, serde_humantime, serde_url,
+    bytes_to_text, override_if_present, override_option_duration_with_option_none,
+    override_option_with_option_none, override_syntax, serde_humantime, serde_url,
 };
 
 #[derive(Debug, Deserialize, Serialize, Clone)]
@@ -57,6 +59,7 @@ pub const NAME: &str = "modern_paste";
 pub const INFO: &str = r#"Modern Paste backend.
 Supports servers running <https://github.com/LINKIWI/modern-paste/>.
 Example popular instance of this is <https://paste.fedoraproject.org/>.
+The api only accepts text, so binary (non utf-8) input is rejected.
 
 Example config block:
 
@@ -74,6 +77,41 @@ Example config block:
     apikey = "BbK1F09sZZXL2335iqDGvGeQswQUcvUmzxMoWjp3yvZDxpWwRiP4YQL6PiUA8gy2"
 "#;
 
+pub const CAPABILITIES: Capabilities = Capabilities {
+    title: true,
+    password: true,
+    auth: true,
+    syntax: SyntaxKind::Given,
+    expiry: ExpiryKind::Duration,
+    delete: true,
+    fetch: true,
+    ..Capabilities::NONE
+};
+
+/// CodeMirror mode names, which modern_paste uses for languages.
+pub const SYNTAXES: &[(&str, &str)] = &[
+    ("bash", "shell"),
+    ("css", "css"),
+    ("diff", "diff"),
+    ("go", "go"),
+    ("haskell", "haskell"),
+    ("html", "htmlmixed"),
+    ("javascript", "javascript"),
+    ("lua", "lua"),
+    ("markdown", "markdown"),
+    ("perl", "perl"),
+    ("php", "php"),
+    ("python", "python"),
+    ("ruby", "ruby"),
+    ("rust", "rust"),
+    ("sql", "sql"),
+    ("swift", "swift"),
+    ("text", "text"),
+    ("toml", "toml"),
+    ("xml", "xml"),
+    ("yaml", "yaml"),
+];
+
 impl PasteClient for Backend {
     fn apply_args(&mut self, args: Vec<String>) -> clap::Result<()> {
         let opt = Opt::from_iter_safe(args)?;
@@ -81,17 +119,17 @@ impl PasteClient for Backend {
         override_option_with_option_none(&mut self.title, opt.title);
         override_option_with_option_none(&mut self.password, opt.password);
         override_option_with_option_none(&mut self.apikey, opt.apikey);
-        override_option_with_option_none(&mut self.syntax, opt.syntax);
+        override_syntax(&mut self.syntax, opt.syntax);
         override_option_duration_with_option_none(&mut self.expires, opt.expires)?;
         Ok(())
     }
 
-    fn paste(&self, data: String) -> PasteResult<Url> {
-        let client = Client::new();
+    fn paste(&self, client_config: &ClientConfig, data: PasteData) -> PasteResult<PasteInfo> {
+        let client = client_config.build()?;
 
         let params = PasteParams {
             api_key: self.apikey.clone(),
-            contents: data,
+            contents: bytes_to_text(data.into_bytes()?, NAME)?,
             expiry_time: match self.expires {
                 None => None,
                 Some(duration) => {
@@ -103,14 +141,19 @@ impl PasteClient for Backend {
                     Some(expires.as_secs())
                 }
             },
-            language: self.syntax.clone(),
+            language: syntax::to_send(&self.syntax).map(|s| s.to_owned()),
             password: self.password.clone(),
             title: self.title.clone(),
         };
 
         let mut api_endpoint: Url = self.url.clone();
         api_endpoint.set_path("/api/paste/submit");
-        let data: PasteResponse = client.post(api_endpoint).json(&params).send()?.json()?;
+        let data: PasteResponse = client
+            .post(api_endpoint)
+            .json(&params)
+            .send()?
+            .check_status()?
+            .json()?;
 
         if let Some(false) = data.success {
             return Err(format!("api returned failure: {:?}", data.message).into());
@@ -119,11 +162,119 @@ impl PasteClient for Backend {
         match data.url {
             None => Err("no url returned in response".to_owned().into()),
             Some(ref url) => {
-                let url = Url::parse(url)?;
-                Ok(url)
+                let mut info = PasteInfo::from(Url::parse(url)?);
+                let mut raw_url = info.url.clone();
+                raw_url.set_path(&format!("/paste/{}/raw", paste_id(&info.url)?));
+                info.raw_url = Some(raw_url);
+                info.delete_token = data.deactivation_token;
+                info.expires = data
+                    .expiry_time
+                    .map(|t| SystemTime::UNIX_EPOCH + Duration::from_secs(t));
+                info.password_required = self.password.is_some();
+                Ok(info)
             }
         }
     }
+
+    fn default_title(&mut self, title: &str) {
+        if self.title.is_none() {
+            self.title = Some(title.to_owned());
+        }
+    }
+
+    fn default_syntax(&mut self, syntax: &str) {
+        if self.syntax.is_none() {
+            self.syntax = Some(syntax.to_owned());
+        }
+    }
+
+    fn title(&self) -> Option<&str> {
+        self.title.as_deref()
+    }
+
+    fn set_syntax(&mut self, syntax: Option<&str>) {
+        self.syntax = syntax.map(|s| s.to_owned());
+    }
+
+    fn syntax(&self) -> Option<&str> {
+        self.syntax.as_deref()
+    }
+
+    fn host(&self) -> Option<String> {
+        self.url.host_str().map(|h| h.to_owned())
+    }
+
+    fn fetch(&self, client_config: &ClientConfig, url: &Url) -> PasteResult<Vec<u8>> {
+        let params = DetailsParams {
+            paste_id: paste_id(url)?,
+            password: self.password.clone(),
+        };
+
+        let mut api_endpoint: Url = self.url.clone();
+        api_endpoint.set_path("/api/paste/details");
+        let data: DetailsResponse = client_config
+            .build()?
+            .post(api_endpoint)
+            .json(&params)
+            .send()?
+            .check_status()?
+            .json()?;
+
+        if let Some(false) = data.success {
+            return Err(format!("api returned failure: {:?}", data.message).into());
+        }
+
+        match data.details {
+            None => Err("no paste details returned in response".to_owned().into()),
+            Some(details) => Ok(details.contents.into_bytes()),
+        }
+    }
+
+    fn delete(
+        &self,
+        client_config: &ClientConfig,
+        url: &Url,
+        token: Option<&str>,
+    ) -> PasteResult<()> {
+        // either the deactivation token or the api key of the paste owner is accepted
+        if token.is_none() && self.apikey.is_none() {
+            return Err(
+                "a deactivation token or apikey is required to delete a paste"
+                    .to_owned()
+                    .into(),
+            );
+        }
+
+        let params = DeactivateParams {
+            paste_id: paste_id(url)?,
+            deactivation_token: token.map(|t| t.to_owned()),
+            api_key: self.apikey.clone(),
+        };
+
+        let mut api_endpoint: Url = self.url.clone();
+        api_endpoint.set_path("/api/paste/deactivate");
+        let data: PasteResponse = client_config
+            .build()?
+            .post(api_endpoint)
+            .json(&params)
+            .send()?
+            .check_status()?
+            .json()?;
+
+        if let Some(false) = data.success {
+            return Err(format!("api returned failure: {:?}", data.message).into());
+        }
+
+        Ok(())
+    }
+}
+
+/// Paste urls are of the form /paste/<paste_id>.
+fn paste_id(url: &Url) -> PasteResult<String> {
+    match url.path_segments().and_then(|mut s| s.next_back()) {
+        Some(id) if !id.is_empty() => Ok(id.to_owned()),
+        _ => Err(format!("could not find paste id in url: {}", url).into()),
+    }
 }
 
 #[derive(Debug, Deserialize, Serialize)]
@@ -144,6 +295,8 @@ struct PasteResponse {
     message: Option<String>,
     failure_name: Option<String>,
     url: Option<String>,
+    expiry_time: Option<u64>,
+    deactivation_token: Option<String>,
 }
 
 impl Display for Backend {
@@ -151,3 +304,28 @@ impl Display for Backend {
         write!(f, "modern_paste | {}", self.url)
     }
 }
+
+#[derive(Debug, Deserialize, Serialize)]
+struct DetailsParams {
+    paste_id: String,
+    password: Option<String>,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+struct DeactivateParams {
+    paste_id: String,
+    deactivation_token: Option<String>,
+    api_key: Option<String>,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+struct DetailsResponse {
+    success: Option<bool>,
+    message: Option<String>,
+    details: Option<PasteDetails>,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+struct PasteDetails {
+    contents: String,
+}
@@ -0,0 +1,312 @@
+use std::fmt::{self, Display, Formatter};
+use std::str::FromStr;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use reqwest::multipart::{Form, Part};
+use reqwest::Response;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use structopt::StructOpt;
+use url::Url;
+
+use crate::backends::{Capabilities, ExpiryKind};
+use crate::client::{ClientConfig, ResponseExt};
+use crate::error::PasteResult;
+use crate::types::{PasteClient, PasteData, PasteInfo};
+use crate::utils::{override_if_present, override_option_with_option_none, serde_url};
+
+/// When an upload expires: a number of hours from now, or a fixed time.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum NullPointerExpires {
+    Hours(u64),
+    At(SystemTime),
+}
+
+impl NullPointerExpires {
+    /// Value of the expires field: hours, or a unix epoch in milliseconds.
+    fn field(self) -> String {
+        match self {
+            NullPointerExpires::Hours(hours) => hours.to_string(),
+            NullPointerExpires::At(time) => time
+                .duration_since(UNIX_EPOCH)
+                .map(|since| since.as_millis())
+                .unwrap_or(0)
+                .to_string(),
+        }
+    }
+
+    fn time(self) -> SystemTime {
+        match self {
+            NullPointerExpires::Hours(hours) => {
+                SystemTime::now() + Duration::from_secs(hours * 60 * 60)
+            }
+            NullPointerExpires::At(time) => time,
+        }
+    }
+}
+
+impl Display for NullPointerExpires {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match self {
+            NullPointerExpires::Hours(hours) => write!(f, "{}", hours),
+            NullPointerExpires::At(time) => write!(f, "{}", humantime::format_rfc3339(*time)),
+        }
+    }
+}
+
+impl FromStr for NullPointerExpires {
+    type Err = String;
+
+    /// A number of hours, a duration in whole hours (eg. "3days"), a rfc3339 time, or a unix epoch
+    /// in seconds prefixed with @, as date(1) takes it.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if let Ok(hours) = s.parse() {
+            return Ok(NullPointerExpires::Hours(hours));
+        }
+        if let Some(epoch) = s.strip_prefix('@') {
+            return match epoch.parse() {
+                Ok(secs) => Ok(NullPointerExpires::At(
+                    UNIX_EPOCH + Duration::from_secs(secs),
+                )),
+                Err(_) => Err(format!("{} (expected seconds since the epoch after @)", s)),
+            };
+        }
+        if let Ok(time) = humantime::parse_rfc3339_weak(s) {
+            return Ok(NullPointerExpires::At(time));
+        }
+        match humantime::parse_duration(s) {
+            Ok(duration) if duration.as_secs() % (60 * 60) == 0 && duration.subsec_nanos() == 0 => {
+                Ok(NullPointerExpires::Hours(duration.as_secs() / (60 * 60)))
+            }
+            Ok(_) => Err(format!(
+                "{} (the server counts lifetimes in whole hours, eg. 36h or 2days)",
+                s
+            )),
+            Err(_) => Err(format!(
+                "{} (expected hours, a duration, a time such as 2030-01-01T00:00:00Z, or @<epoch \
+                 seconds>)",
+                s
+            )),
+        }
+    }
+}
+
+impl Serialize for NullPointerExpires {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self {
+            NullPointerExpires::Hours(hours) => serializer.serialize_u64(*hours),
+            NullPointerExpires::At(_) => serializer.serialize_str(&self.to_string()),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for NullPointerExpires {
+    fn deserialize<D: Deserializer<'de>>(d: D) -> Result<Self, D::Error> {
+        // hours may be written as a toml integer or as a string
+        #[derive(Deserialize)]
+        #[serde(untagged)]
+        enum Raw {
+            Hours(u64),
+            Text(String),
+        }
+        match Raw::deserialize(d)? {
+            Raw::Hours(hours) => Ok(NullPointerExpires::Hours(hours)),
+            Raw::Text(s) => s
+                .parse()
+                .map_err(|e| serde::de::Error::custom(format!("invalid expires: {}", e))),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone)]
+#[serde(deny_unknown_fields)]
+#[serde(rename_all = "snake_case")]
+pub struct Backend {
+    #[serde(with = "serde_url")]
+    pub url: Url,
+    pub expires: Option<NullPointerExpires>,
+    #[serde(default)]
+    pub secret: bool,
+    pub filename: Option<String>,
+}
+
+#[derive(Debug, StructOpt)]
+#[structopt(about = "null_pointer backend")]
+#[structopt(template = "{about}\n\nUSAGE:\n    {usage}\n\n{all-args}")]
+pub struct Opt {
+    /// Overrides url set in config
+    #[structopt(short = "u", long = "url")]
+    url: Option<Url>,
+    /// Time to live: hours, a duration in whole hours, a rfc3339 time, or @<epoch seconds>
+    #[structopt(short = "e", long = "expires", value_name = "expiry|NONE")]
+    expires: Option<String>,
+    /// Get a long, hard to guess url
+    #[structopt(short = "S", long = "secret", conflicts_with = "no_secret")]
+    secret: bool,
+    /// Get a short url, even if secret is set in the config
+    #[structopt(long = "no-secret")]
+    no_secret: bool,
+    /// File name to upload as, which the server takes the url's extension from
+    #[structopt(short = "n", long = "filename", value_name = "filename|NONE")]
+    filename: Option<String>,
+}
+
+pub const NAME: &str = "null_pointer";
+
+pub const INFO: &str = r#"The Null Pointer backend.
+Supports <https://0x0.st/>, <https://envs.sh/> and other servers running The Null Pointer
+<https://git.0x0.st/mia/0x0>. Any file can be uploaded, binary or text.
+
+The server returns a management token with each new upload, which pc keeps as the delete token
+(in the tokenfile or histfile, or printed if neither is set), so `pc delete <url>` works later on.
+The same token changes the expiry of an upload:
+
+    curl -F token=<token> -F expires=<hours> <url>
+
+Example config block:
+
+    [servers.0x0]
+    backend = "null_pointer"
+    url = "https://0x0.st/"
+
+    # Optional values
+
+    # When the upload expires: a number of hours, a duration in whole hours, a rfc3339 time, or a
+    # unix epoch in seconds such as "@1893456000". Default is set by the server from the file size.
+    expires = 24
+
+    # Use a long, hard to guess url. Default false.
+    secret = true
+
+    # File name to upload as; the url gets its extension. Default is the input file name, or
+    # guessed by the server from the content for stdin.
+    filename = "debug.log"
+"#;
+
+pub const CAPABILITIES: Capabilities = Capabilities {
+    expiry: ExpiryKind::Duration,
+    delete: true,
+    fetch: true,
+    ..Capabilities::NONE
+};
+
+/// File name for content from stdin, when none is configured. The server only treats parts with
+/// a file name as uploads, and guesses the extension when this has none.
+const DEFAULT_FILENAME: &str = "file";
+
+impl PasteClient for Backend {
+    fn apply_args(&mut self, args: Vec<String>) -> clap::Result<()> {
+        let opt = Opt::from_iter_safe(args)?;
+        override_if_present(&mut self.url, opt.url);
+        override_option_with_option_none(&mut self.filename, opt.filename);
+        if opt.secret {
+            self.secret = true;
+        }
+        if opt.no_secret {
+            self.secret = false;
+        }
+        if let Some(expires) = opt.expires {
+            self.expires = match expires.as_str() {
+                "NONE" => None,
+                _ => Some(expires.parse().map_err(|e| clap::Error {
+                    message: format!("Invalid value for expires: {}", e),
+                    kind: clap::ErrorKind::InvalidValue,
+                    info: None,
+                })?),
+            };
+        }
+        Ok(())
+    }
+
+    fn paste(&self, client_config: &ClientConfig, data: PasteData) -> PasteResult<PasteInfo> {
+        let filename = self.filename.as_deref().unwrap_or(DEFAULT_FILENAME);
+        let mut form = Form::new().part(
+            "file",
+            Part::bytes(data.into_bytes()?).file_name(filename.to_owned()),
+        );
+        if let Some(expires) = self.expires {
+            form = form.text("expires", expires.field());
+        }
+        if self.secret {
+            form = form.text("secret", "");
+        }
+
+        let mut res = client_config
+            .build()?
+            .post(self.url.clone())
+            .multipart(form)
+            .send()?
+            .check_status()?;
+        let status = res.status();
+        let text = res.text()?;
+        if !status.is_success() {
+            return Err(format!("server returned failure: {} ({})", text.trim(), status).into());
+        }
+
+        let mut info = PasteInfo::from(Url::parse(text.trim())?);
+        info.raw_url = Some(info.url.clone());
+        // only sent for new uploads; the same content again gets the url it had before
+        info.delete_token = header(&res, "X-Token");
+        // the server picks a default lifetime from the file size, and says what it chose
+        info.expires = match header(&res, "X-Expires").and_then(|ms| ms.parse().ok()) {
+            Some(ms) => Some(UNIX_EPOCH + Duration::from_millis(ms)),
+            None => self.expires.map(NullPointerExpires::time),
+        };
+        Ok(info)
+    }
+
+    fn default_file_name(&mut self, name: &str) {
+        if self.filename.is_none() {
+            self.filename = Some(name.to_owned());
+        }
+    }
+
+    fn host(&self) -> Option<String> {
+        self.url.host_str().map(|h| h.to_owned())
+    }
+
+    fn fetch(&self, client_config: &ClientConfig, url: &Url) -> PasteResult<Vec<u8>> {
+        client_config.build()?.get(url.clone()).send()?.read_body()
+    }
+
+    fn delete(
+        &self,
+        client_config: &ClientConfig,
+        url: &Url,
+        token: Option<&str>,
+    ) -> PasteResult<()> {
+        let token = match token {
+            Some(token) => token,
+            None => {
+                return Err(
+                    "deleting an upload needs the token the server returned for it"
+                        .to_owned()
+                        .into(),
+                );
+            }
+        };
+        let form = Form::new()
+            .text("token", token.to_owned())
+            .text("delete", "");
+        client_config
+            .build()?
+            .post(url.clone())
+            .multipart(form)
+            .send()?
+            .check_status()?
+            .error_for_status()?;
+        Ok(())
+    }
+}
+
+fn header(res: &Response, name: &str) -> Option<String> {
+    res.headers()
+        .get(name)
+        .and_then(|v| v.to_str().ok())
+        .map(|v| v.trim().to_owned())
+}
+
+impl Display for Backend {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(f, "null_pointer | {}", self.url)
+    }
+}
@@ -1,14 +1,15 @@
 use std::fmt::{self, Display, Formatter};
-use std::time::Duration;
+use std::time::{Duration, SystemTime};
 
-use reqwest::multipart::Form;
-use reqwest::Client;
+use reqwest::multipart::{Form, Part};
 use serde::{Deserialize, Serialize};
 use structopt::StructOpt;
 use url::Url;
 
+use crate::backends::{Capabilities, ExpiryKind};
+use crate::client::{ClientConfig, ResponseExt};
 use crate::error::PasteResult;
-use crate::types::PasteClient;
+use crate::types::{PasteClient, PasteData, PasteInfo};
 use crate::utils::{
     override_if_present, override_option_duration_with_option_none,
     override_option_with_option_none, serde_humantime, serde_url,
@@ -32,7 +33,7 @@ pub struct Backend {
 #[derive(Debug, StructOpt)]
 #[structopt(about = "onetimesecret backend")]
 #[structopt(template = "{about}\n\nUSAGE:\n    {usage}\n\n{all-args}")]
-struct Opt {
+pub struct Opt {
     /// Overrides url set in config
     #[structopt(short = "u", long = "url")]
     url: Option<Url>,
@@ -80,6 +81,14 @@ Example config block:
     apikey = "DEADBEEF"
 "#;
 
+pub const CAPABILITIES: Capabilities = Capabilities {
+    password: true,
+    auth: true,
+    expiry: ExpiryKind::Duration,
+    delete: true,
+    ..Capabilities::NONE
+};
+
 impl PasteClient for Backend {
     fn apply_args(&mut self, args: Vec<String>) -> clap::Result<()> {
         let opt = Opt::from_iter_safe(args)?;
@@ -92,8 +101,8 @@ impl PasteClient for Backend {
         Ok(())
     }
 
-    fn paste(&self, data: String) -> PasteResult<Url> {
-        let form = Form::new().text("secret", data);
+    fn paste(&self, client_config: &ClientConfig, data: PasteData) -> PasteResult<PasteInfo> {
+        let form = Form::new().part("secret", Part::bytes(data.into_bytes()?));
         let form = match self.password {
             Some(ref password) => form.text("passphrase", password.to_owned()),
             None => form,
@@ -110,7 +119,7 @@ impl PasteClient for Backend {
         let mut api_endpoint: Url = self.url.clone();
         api_endpoint.set_path("/api/v1/share");
 
-        let request = Client::new().post(api_endpoint).multipart(form);
+        let request = client_config.build()?.post(api_endpoint).multipart(form);
 
         let request = match (&self.username, &self.apikey) {
             (None, None) => request,
@@ -124,13 +133,10 @@ impl PasteClient for Backend {
             }
         };
 
-        let data: String = request.send()?.text()?;
+        let data: String = request.send()?.check_status()?.text()?;
 
         let data: PasteResponse = match serde_json::from_str(&data) {
-            Ok(data) => {
-                dbg!(&data);
-                data
-            }
+            Ok(data) => data,
             Err(_) => {
                 return Err(format!("api response: {}", data).into());
             }
@@ -138,7 +144,66 @@ impl PasteClient for Backend {
 
         let mut url: Url = self.url.clone();
         url.set_path(&format!("/secret/{}", data.secret_key));
-        Ok(url)
+        let mut manage_url: Url = self.url.clone();
+        manage_url.set_path(&format!("/private/{}", data.metadata_key));
+
+        let mut info = PasteInfo::from(url);
+        info.manage_url = Some(manage_url);
+        info.expires = Some(SystemTime::now() + Duration::from_secs(data.secret_ttl));
+        info.password_required = data.passphrase_required;
+        info.extras
+            .insert("metadata_key".to_owned(), data.metadata_key.clone());
+        info.extras
+            .insert("secret_ttl".to_owned(), data.secret_ttl.to_string());
+        info.extras
+            .insert("metadata_ttl".to_owned(), data.metadata_ttl.to_string());
+        info.delete_token = Some(data.metadata_key);
+        Ok(info)
+    }
+
+    fn host(&self) -> Option<String> {
+        self.url.host_str().map(|h| h.to_owned())
+    }
+
+    fn delete(
+        &self,
+        client_config: &ClientConfig,
+        _url: &Url,
+        token: Option<&str>,
+    ) -> PasteResult<()> {
+        // secrets are burned through the metadata key; the secret key in the url can't be used
+        let metadata_key = match token {
+            Some(token) => token,
+            None => {
+                return Err("the metadata key is required to burn a secret"
+                    .to_owned()
+                    .into());
+            }
+        };
+
+        let mut api_endpoint: Url = self.url.clone();
+        api_endpoint.set_path(&format!("/api/v1/private/{}/burn", metadata_key));
+
+        let request = client_config.build()?.post(api_endpoint);
+
+        let request = match (&self.username, &self.apikey) {
+            (None, None) => request,
+            (Some(ref username), Some(ref apikey)) => request.basic_auth(username, Some(apikey)),
+            (_, _) => {
+                return Err(
+                    "Either both username and apikey must be provided, or neither."
+                        .to_owned()
+                        .into(),
+                );
+            }
+        };
+
+        let data: String = request.send()?.check_status()?.text()?;
+
+        match serde_json::from_str::<BurnResponse>(&data) {
+            Ok(_) => Ok(()),
+            Err(_) => Err(format!("api response: {}", data).into()),
+        }
     }
 }
 
@@ -161,3 +226,9 @@ struct PasteResponse {
     created: u64,
     updated: u64,
 }
+
+#[derive(Debug, Deserialize, Serialize)]
+struct BurnResponse {
+    state: serde_json::Value,
+    secret_shortkey: Option<String>,
+}
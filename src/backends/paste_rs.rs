@@ -1,12 +1,13 @@
 use std::fmt::{self, Display, Formatter};
 
-use reqwest::Client;
 use serde::{Deserialize, Serialize};
 use structopt::StructOpt;
 use url::Url;
 
+use crate::backends::Capabilities;
+use crate::client::{ClientConfig, ResponseExt};
 use crate::error::PasteResult;
-use crate::types::PasteClient;
+use crate::types::{PasteClient, PasteData, PasteInfo};
 use crate::utils::{override_if_present, serde_url};
 
 #[derive(Debug, Deserialize, Serialize, Clone)]
@@ -20,7 +21,7 @@ pub struct Backend {
 #[derive(Debug, StructOpt)]
 #[structopt(about = "paste_rs backend")]
 #[structopt(template = "{about}\n\nUSAGE:\n    {usage}\n\n{all-args}")]
-struct Opt {
+pub struct Opt {
     /// Overrides url set in config
     #[structopt(short = "u", long = "url")]
     url: Option<Url>,
@@ -41,6 +42,12 @@ Example config block:
     url = "https://paste.rs/"
 "#;
 
+pub const CAPABILITIES: Capabilities = Capabilities {
+    delete: true,
+    fetch: true,
+    ..Capabilities::NONE
+};
+
 impl PasteClient for Backend {
     fn apply_args(&mut self, args: Vec<String>) -> clap::Result<()> {
         let opt = Opt::from_iter_safe(args)?;
@@ -48,11 +55,41 @@ impl PasteClient for Backend {
         Ok(())
     }
 
-    fn paste(&self, data: String) -> PasteResult<Url> {
-        let client = Client::new();
-        let text = client.post(self.url.clone()).body(data).send()?.text()?;
+    fn paste(&self, client_config: &ClientConfig, data: PasteData) -> PasteResult<PasteInfo> {
+        let client = client_config.build()?;
+        let text = client
+            .post(self.url.clone())
+            .body(data.into_body())
+            .send()?
+            .check_status()?
+            .text()?;
         let url = Url::parse(&text)?;
-        Ok(url)
+        let mut info = PasteInfo::from(url);
+        info.raw_url = Some(info.url.clone());
+        Ok(info)
+    }
+
+    fn host(&self) -> Option<String> {
+        self.url.host_str().map(|h| h.to_owned())
+    }
+
+    fn fetch(&self, client_config: &ClientConfig, url: &Url) -> PasteResult<Vec<u8>> {
+        client_config.build()?.get(url.clone()).send()?.read_body()
+    }
+
+    fn delete(
+        &self,
+        client_config: &ClientConfig,
+        url: &Url,
+        _token: Option<&str>,
+    ) -> PasteResult<()> {
+        client_config
+            .build()?
+            .delete(url.clone())
+            .send()?
+            .check_status()?
+            .error_for_status()?;
+        Ok(())
     }
 }
 
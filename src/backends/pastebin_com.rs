@@ -0,0 +1,511 @@
+use std::fmt::{self, Display, Formatter};
+use std::str::FromStr;
+use std::time::{Duration, SystemTime};
+
+use serde::{Deserialize, Serialize};
+use structopt::StructOpt;
+use url::Url;
+
+use crate::backends::{Capabilities, ExpiryKind, SyntaxKind};
+use crate::client::{ClientConfig, ResponseExt};
+use crate::error::PasteResult;
+use crate::syntax;
+use crate::types::{PasteClient, PasteData, PasteInfo};
+use crate::utils::{
+    bytes_to_text, override_if_present, override_option_with_option_none, override_syntax,
+    serde_url,
+};
+
+/// The lifetimes pastebin.com offers, named as in its api.
+#[derive(Debug, Deserialize, Serialize, Copy, Clone, PartialEq)]
+pub enum PastebinExpires {
+    #[serde(rename = "N")]
+    Never,
+    #[serde(rename = "10M")]
+    TenMinutes,
+    #[serde(rename = "1H")]
+    Hour,
+    #[serde(rename = "1D")]
+    Day,
+    #[serde(rename = "1W")]
+    Week,
+    #[serde(rename = "2W")]
+    TwoWeeks,
+    #[serde(rename = "1M")]
+    Month,
+    #[serde(rename = "6M")]
+    SixMonths,
+    #[serde(rename = "1Y")]
+    Year,
+}
+
+impl PastebinExpires {
+    const ALL: [PastebinExpires; 9] = [
+        PastebinExpires::Never,
+        PastebinExpires::TenMinutes,
+        PastebinExpires::Hour,
+        PastebinExpires::Day,
+        PastebinExpires::Week,
+        PastebinExpires::TwoWeeks,
+        PastebinExpires::Month,
+        PastebinExpires::SixMonths,
+        PastebinExpires::Year,
+    ];
+
+    /// How long a paste is kept for. Months and years are as humantime counts them, so that
+    /// "1month" or "6months" can be given instead of the api's names.
+    fn duration(self) -> Option<Duration> {
+        let minutes = match self {
+            PastebinExpires::Never => return None,
+            PastebinExpires::TenMinutes => 10,
+            PastebinExpires::Hour => 60,
+            PastebinExpires::Day => 24 * 60,
+            PastebinExpires::Week => 7 * 24 * 60,
+            PastebinExpires::TwoWeeks => 14 * 24 * 60,
+            PastebinExpires::Month => return Some(Duration::from_secs(2_630_016)),
+            PastebinExpires::SixMonths => return Some(Duration::from_secs(6 * 2_630_016)),
+            PastebinExpires::Year => return Some(Duration::from_secs(31_557_600)),
+        };
+        Some(Duration::from_secs(minutes * 60))
+    }
+}
+
+impl Display for PastebinExpires {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                PastebinExpires::Never => "N",
+                PastebinExpires::TenMinutes => "10M",
+                PastebinExpires::Hour => "1H",
+                PastebinExpires::Day => "1D",
+                PastebinExpires::Week => "1W",
+                PastebinExpires::TwoWeeks => "2W",
+                PastebinExpires::Month => "1M",
+                PastebinExpires::SixMonths => "6M",
+                PastebinExpires::Year => "1Y",
+            }
+        )
+    }
+}
+
+impl FromStr for PastebinExpires {
+    type Err = String;
+
+    /// One of the api's names for the lifetimes, "never", or a duration that is exactly one of
+    /// them, such as "10min" or "2weeks".
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if let Some(expires) = PastebinExpires::ALL
+            .iter()
+            .find(|e| e.to_string() == s || (s == "never" && **e == PastebinExpires::Never))
+        {
+            return Ok(*expires);
+        }
+        let expected = "expected N (never), 10M, 1H, 1D, 1W, 2W, 1M, 6M or 1Y, or the same as \
+                        a duration, eg. 10min or 2weeks";
+        let duration = humantime::parse_duration(s).map_err(|_| format!("{} ({})", s, expected))?;
+        match PastebinExpires::ALL
+            .iter()
+            .find(|e| e.duration() == Some(duration))
+        {
+            Some(expires) => Ok(*expires),
+            None => Err(format!(
+                "{} is not one of the lifetimes pastebin.com offers ({})",
+                s, expected
+            )),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, Serialize, Copy, Clone, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum PastebinPrivacy {
+    Public,
+    Unlisted,
+    Private,
+}
+
+impl PastebinPrivacy {
+    /// Value of api_paste_private.
+    fn code(self) -> &'static str {
+        match self {
+            PastebinPrivacy::Public => "0",
+            PastebinPrivacy::Unlisted => "1",
+            PastebinPrivacy::Private => "2",
+        }
+    }
+}
+
+impl FromStr for PastebinPrivacy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "public" => Ok(PastebinPrivacy::Public),
+            "unlisted" => Ok(PastebinPrivacy::Unlisted),
+            "private" => Ok(PastebinPrivacy::Private),
+            _ => Err(format!("{} (expected public, unlisted or private)", s)),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone)]
+#[serde(deny_unknown_fields)]
+#[serde(rename_all = "snake_case")]
+pub struct Backend {
+    #[serde(with = "serde_url")]
+    pub url: Url,
+    pub api_dev_key: String,
+    pub api_user_key: Option<String>,
+    pub username: Option<String>,
+    pub password: Option<String>,
+    pub title: Option<String>,
+    pub syntax: Option<String>,
+    pub privacy: Option<PastebinPrivacy>,
+    pub expires: Option<PastebinExpires>,
+}
+
+#[derive(Debug, StructOpt)]
+#[structopt(about = "pastebin_com backend")]
+#[structopt(template = "{about}\n\nUSAGE:\n    {usage}\n\n{all-args}")]
+pub struct Opt {
+    /// Overrides url set in config
+    #[structopt(short = "u", long = "url")]
+    url: Option<Url>,
+    /// Developer api key, from https://pastebin.com/doc_api
+    #[structopt(short = "k", long = "api-dev-key", value_name = "key")]
+    api_dev_key: Option<String>,
+    /// User key to paste as, instead of logging in with username and password
+    #[structopt(short = "K", long = "api-user-key", value_name = "key|NONE")]
+    api_user_key: Option<String>,
+    /// Username to log in and paste as
+    #[structopt(short = "U", long = "username", value_name = "username|NONE")]
+    username: Option<String>,
+    /// Password to log in with
+    #[structopt(long = "password", value_name = "password|NONE")]
+    password: Option<String>,
+    /// Name for the paste
+    #[structopt(short = "t", long = "title", value_name = "title|NONE")]
+    title: Option<String>,
+    /// Format for syntax highlighting
+    #[structopt(short = "s", long = "syntax", value_name = "filetype|NONE")]
+    syntax: Option<String>,
+    /// Who can see the paste. Private pastes need a user login
+    #[structopt(
+        short = "p",
+        long = "privacy",
+        value_name = "public|unlisted|private|NONE"
+    )]
+    privacy: Option<String>,
+    /// Time to live: N (never), 10M, 1H, 1D, 1W, 2W, 1M, 6M or 1Y
+    #[structopt(short = "e", long = "expires", value_name = "expiry|NONE")]
+    expires: Option<String>,
+}
+
+pub const NAME: &str = "pastebin_com";
+
+pub const INFO: &str = r#"Pastebin.com backend.
+Supports <https://pastebin.com/>, with a developer api key from <https://pastebin.com/doc_api>.
+Pastes are anonymous unless a user key is given, or a username and password to get one by
+logging in. Deleting pastes, and private pastes, need a user. The api only accepts text, so
+binary (non utf-8) input is rejected.
+
+Example config block:
+
+    [servers.pastebin]
+    backend = "pastebin_com"
+    url = "https://pastebin.com/"
+    api_dev_key = "d5f8b1e7c3a94e2f8b6d0a1c7e3f9b24"
+
+    # Optional values
+
+    # Paste as a user, with their user key...
+    api_user_key = "6c1f3e5a7b9d2f4e8a0c1b3d5f7e9a2c"
+    # ...or by logging in to get one.
+    username = "myuser"
+    password = "hunter2"
+
+    # Name of the paste. Default is the input file name, if any.
+    title = "build logs"
+
+    # Format for syntax highlighting.
+    syntax = "python"
+
+    # public, unlisted or private. Private pastes need a user. Default is set by the server
+    # (public for anonymous pastes, or the user's default).
+    privacy = "unlisted"
+
+    # One of N (never), 10M, 1H, 1D, 1W, 2W, 1M, 6M or 1Y. Durations like "10min" are also
+    # accepted on the command line if they are exactly one of these. Default is never.
+    expires = "1W"
+"#;
+
+pub const CAPABILITIES: Capabilities = Capabilities {
+    title: true,
+    expiry: ExpiryKind::Choice,
+    auth: true,
+    syntax: SyntaxKind::Given,
+    delete: true,
+    fetch: true,
+    max_size: Some(512 * 1024),
+    ..Capabilities::NONE
+};
+
+/// Format names pastebin.com uses for languages. It has no format for toml.
+pub const SYNTAXES: &[(&str, &str)] = &[
+    ("bash", "bash"),
+    ("c", "c"),
+    ("cpp", "cpp"),
+    ("csharp", "csharp"),
+    ("css", "css"),
+    ("diff", "diff"),
+    ("go", "go"),
+    ("haskell", "haskell"),
+    ("html", "html5"),
+    ("ini", "ini"),
+    ("java", "java"),
+    ("javascript", "javascript"),
+    ("json", "json"),
+    ("kotlin", "kotlin"),
+    ("latex", "latex"),
+    ("lua", "lua"),
+    ("markdown", "markdown"),
+    ("perl", "perl"),
+    ("php", "php"),
+    ("python", "python"),
+    ("ruby", "ruby"),
+    ("rust", "rust"),
+    ("scala", "scala"),
+    ("sql", "sql"),
+    ("swift", "swift"),
+    ("text", "text"),
+    ("typescript", "typescript"),
+    ("vim", "vim"),
+    ("xml", "xml"),
+    ("yaml", "yaml"),
+];
+
+impl PasteClient for Backend {
+    fn apply_args(&mut self, args: Vec<String>) -> clap::Result<()> {
+        let opt = Opt::from_iter_safe(args)?;
+        override_if_present(&mut self.url, opt.url);
+        override_if_present(&mut self.api_dev_key, opt.api_dev_key);
+        override_option_with_option_none(&mut self.api_user_key, opt.api_user_key);
+        override_option_with_option_none(&mut self.username, opt.username);
+        override_option_with_option_none(&mut self.password, opt.password);
+        override_option_with_option_none(&mut self.title, opt.title);
+        override_syntax(&mut self.syntax, opt.syntax);
+        if let Some(privacy) = opt.privacy {
+            self.privacy = match privacy.as_str() {
+                "NONE" => None,
+                _ => Some(privacy.parse().map_err(|e| clap::Error {
+                    message: format!("Invalid value for privacy: {}", e),
+                    kind: clap::ErrorKind::InvalidValue,
+                    info: None,
+                })?),
+            };
+        }
+        if let Some(expires) = opt.expires {
+            self.expires = match expires.as_str() {
+                "NONE" => None,
+                _ => Some(expires.parse().map_err(|e| clap::Error {
+                    message: format!("Invalid value for expires: {}", e),
+                    kind: clap::ErrorKind::InvalidValue,
+                    info: None,
+                })?),
+            };
+        }
+        Ok(())
+    }
+
+    fn paste(&self, client_config: &ClientConfig, data: PasteData) -> PasteResult<PasteInfo> {
+        let user_key = self.user_key(client_config)?;
+        if self.privacy == Some(PastebinPrivacy::Private) && user_key.is_none() {
+            return Err(
+                "private pastes need a user: set api_user_key, or username and password"
+                    .to_owned()
+                    .into(),
+            );
+        }
+
+        let mut params = vec![
+            ("api_dev_key", self.api_dev_key.clone()),
+            ("api_option", "paste".to_owned()),
+            ("api_paste_code", bytes_to_text(data.into_bytes()?, NAME)?),
+        ];
+        if let Some(user_key) = user_key {
+            params.push(("api_user_key", user_key));
+        }
+        if let Some(ref title) = self.title {
+            params.push(("api_paste_name", title.to_owned()));
+        }
+        if let Some(syntax) = syntax::to_send(&self.syntax) {
+            params.push(("api_paste_format", syntax.to_owned()));
+        }
+        if let Some(privacy) = self.privacy {
+            params.push(("api_paste_private", privacy.code().to_owned()));
+        }
+        if let Some(expires) = self.expires {
+            params.push(("api_paste_expire_date", expires.to_string()));
+        }
+
+        let url = Url::parse(&self.api_call(client_config, "api_post.php", &params)?)?;
+        let mut info = PasteInfo::from(url);
+        let mut raw_url = info.url.clone();
+        raw_url.set_path(&format!("/raw/{}", paste_key(&info.url)?));
+        info.raw_url = Some(raw_url);
+        info.expires = self
+            .expires
+            .and_then(|expires| expires.duration())
+            .map(|duration| SystemTime::now() + duration);
+        Ok(info)
+    }
+
+    fn default_title(&mut self, title: &str) {
+        if self.title.is_none() {
+            self.title = Some(title.to_owned());
+        }
+    }
+
+    fn default_syntax(&mut self, syntax: &str) {
+        if self.syntax.is_none() {
+            self.syntax = Some(syntax.to_owned());
+        }
+    }
+
+    fn title(&self) -> Option<&str> {
+        self.title.as_deref()
+    }
+
+    fn set_syntax(&mut self, syntax: Option<&str>) {
+        self.syntax = syntax.map(|s| s.to_owned());
+    }
+
+    fn syntax(&self) -> Option<&str> {
+        self.syntax.as_deref()
+    }
+
+    fn host(&self) -> Option<String> {
+        self.url.host_str().map(|h| h.to_owned())
+    }
+
+    /// Public and unlisted pastes are fetched from /raw/<key>. Private ones aren't shown there,
+    /// so are fetched through the api instead, as the user that made them.
+    fn fetch(&self, client_config: &ClientConfig, url: &Url) -> PasteResult<Vec<u8>> {
+        let key = paste_key(url)?;
+        let mut raw_url = self.url.clone();
+        raw_url.set_path(&format!("/raw/{}", key));
+        let res = client_config.build()?.get(raw_url).send()?.check_status()?;
+
+        if res.status().is_client_error() {
+            if let Some(user_key) = self.user_key(client_config)? {
+                let params = [
+                    ("api_dev_key", self.api_dev_key.clone()),
+                    ("api_user_key", user_key),
+                    ("api_option", "show_paste".to_owned()),
+                    ("api_paste_key", key),
+                ];
+                return Ok(self
+                    .api_call(client_config, "api_raw.php", &params)?
+                    .into_bytes());
+            }
+        }
+
+        res.read_body()
+    }
+
+    fn delete(
+        &self,
+        client_config: &ClientConfig,
+        url: &Url,
+        _token: Option<&str>,
+    ) -> PasteResult<()> {
+        let user_key = match self.user_key(client_config)? {
+            Some(user_key) => user_key,
+            None => {
+                return Err(
+                    "deleting a paste needs the user that made it: set api_user_key, or \
+                     username and password"
+                        .to_owned()
+                        .into(),
+                );
+            }
+        };
+        let params = [
+            ("api_dev_key", self.api_dev_key.clone()),
+            ("api_user_key", user_key),
+            ("api_option", "delete".to_owned()),
+            ("api_paste_key", paste_key(url)?),
+        ];
+        self.api_call(client_config, "api_post.php", &params)?;
+        Ok(())
+    }
+}
+
+impl Backend {
+    /// The user key to paste as: the configured one, or one from logging in with the username
+    /// and password. None for anonymous pastes. Called at most once per paste, fetch or delete,
+    /// as the api limits how often a login can be done.
+    fn user_key(&self, client_config: &ClientConfig) -> PasteResult<Option<String>> {
+        if let Some(ref user_key) = self.api_user_key {
+            return Ok(Some(user_key.to_owned()));
+        }
+        let (username, password) = match (&self.username, &self.password) {
+            (None, None) => return Ok(None),
+            (Some(username), Some(password)) => (username, password),
+            (_, _) => {
+                return Err(
+                    "Either both username and password must be provided, or neither."
+                        .to_owned()
+                        .into(),
+                );
+            }
+        };
+        let params = [
+            ("api_dev_key", self.api_dev_key.clone()),
+            ("api_user_name", username.to_owned()),
+            ("api_user_password", password.to_owned()),
+        ];
+        let user_key = self.api_call(client_config, "api_login.php", &params)?;
+        Ok(Some(user_key))
+    }
+
+    /// Post a form to an api endpoint. The api answers in plain text, with errors starting "Bad
+    /// API request".
+    fn api_call(
+        &self,
+        client_config: &ClientConfig,
+        endpoint: &str,
+        params: &[(&str, String)],
+    ) -> PasteResult<String> {
+        let mut api_url = self.url.clone();
+        api_url.set_path(&format!("/api/{}", endpoint));
+        let text = client_config
+            .build()?
+            .post(api_url)
+            .form(params)
+            .send()?
+            .check_status()?
+            .text()?;
+        let text = text.trim();
+        if text.starts_with("Bad API request") {
+            return Err(format!("api returned failure: {}", text).into());
+        }
+        Ok(text.to_owned())
+    }
+}
+
+/// Paste urls are of the form https://pastebin.com/<paste key>, or /raw/<paste key>.
+fn paste_key(url: &Url) -> PasteResult<String> {
+    match url.path_segments().and_then(|mut s| s.next_back()) {
+        Some(key) if !key.is_empty() => Ok(key.to_owned()),
+        _ => Err(format!("could not find paste key in url: {}", url).into()),
+    }
+}
+
+impl Display for Backend {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(f, "pastebin_com | {}", self.url)
+    }
+}
@@ -1,13 +1,14 @@
 use std::fmt::{self, Display, Formatter};
 
-use reqwest::multipart::Form;
-use reqwest::Client;
+use reqwest::multipart::{Form, Part};
 use serde::{Deserialize, Serialize};
 use structopt::StructOpt;
 use url::Url;
 
+use crate::backends::{Capabilities, SyntaxKind};
+use crate::client::{ClientConfig, ResponseExt};
 use crate::error::PasteResult;
-use crate::types::PasteClient;
+use crate::types::{PasteClient, PasteData, PasteInfo};
 use crate::utils::{override_if_present, serde_url};
 
 #[derive(Debug, Deserialize, Serialize, Clone)]
@@ -39,6 +40,11 @@ Example config block:
     url = "http://p.ip.fi/"
 "#;
 
+pub const CAPABILITIES: Capabilities = Capabilities {
+    syntax: SyntaxKind::Detected,
+    ..Capabilities::NONE
+};
+
 impl PasteClient for Backend {
     fn apply_args(&mut self, args: Vec<String>) -> clap::Result<()> {
         let opt = Opt::from_iter_safe(args)?;
@@ -46,15 +52,21 @@ impl PasteClient for Backend {
         Ok(())
     }
 
-    fn paste(&self, data: String) -> PasteResult<Url> {
-        let form = Form::new().text("paste", data);
-        let text = Client::new()
+    fn paste(&self, client_config: &ClientConfig, data: PasteData) -> PasteResult<PasteInfo> {
+        let form = Form::new().part("paste", Part::bytes(data.into_bytes()?));
+        let text = client_config
+            .build()?
             .post(self.url.clone())
             .multipart(form)
             .send()?
+            .check_status()?
             .text()?;
         let url = Url::parse(&text)?;
-        Ok(url)
+        Ok(url.into())
+    }
+
+    fn host(&self) -> Option<String> {
+        self.url.host_str().map(|h| h.to_owned())
     }
 }
 
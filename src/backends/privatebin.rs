@@ -0,0 +1,649 @@
+use std::fmt::{self, Display, Formatter};
+use std::io::{Read, Write};
+use std::str::FromStr;
+use std::time::{Duration, SystemTime};
+
+use flate2::read::DeflateDecoder;
+use flate2::write::DeflateEncoder;
+use flate2::Compression;
+use openssl::hash::MessageDigest;
+use openssl::pkcs5::pbkdf2_hmac;
+use openssl::rand::rand_bytes;
+use openssl::symm::{decrypt_aead, encrypt_aead, Cipher};
+use reqwest::header::HeaderValue;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use structopt::StructOpt;
+use url::Url;
+
+use crate::backends::{Capabilities, ExpiryKind};
+use crate::client::{ClientConfig, ResponseExt};
+use crate::error::PasteResult;
+use crate::types::{PasteClient, PasteData, PasteInfo};
+use crate::utils::{
+    bytes_to_text, override_if_present, override_option_with_option_none, serde_url,
+};
+
+#[derive(Debug, Deserialize, Serialize, Copy, Clone, PartialEq)]
+pub enum PrivatebinExpires {
+    #[serde(rename = "5min")]
+    FiveMinutes,
+    #[serde(rename = "10min")]
+    TenMinutes,
+    #[serde(rename = "1hour")]
+    Hour,
+    #[serde(rename = "1day")]
+    Day,
+    #[serde(rename = "1week")]
+    Week,
+    #[serde(rename = "1month")]
+    Month,
+    #[serde(rename = "1year")]
+    Year,
+    #[serde(rename = "never")]
+    Never,
+}
+
+impl PrivatebinExpires {
+    /// Roughly how long a paste is kept for.
+    fn duration(self) -> Option<Duration> {
+        let minutes = match self {
+            PrivatebinExpires::FiveMinutes => 5,
+            PrivatebinExpires::TenMinutes => 10,
+            PrivatebinExpires::Hour => 60,
+            PrivatebinExpires::Day => 24 * 60,
+            PrivatebinExpires::Week => 7 * 24 * 60,
+            PrivatebinExpires::Month => 30 * 24 * 60,
+            PrivatebinExpires::Year => 365 * 24 * 60,
+            PrivatebinExpires::Never => return None,
+        };
+        Some(Duration::from_secs(minutes * 60))
+    }
+}
+
+impl Display for PrivatebinExpires {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                PrivatebinExpires::FiveMinutes => "5min",
+                PrivatebinExpires::TenMinutes => "10min",
+                PrivatebinExpires::Hour => "1hour",
+                PrivatebinExpires::Day => "1day",
+                PrivatebinExpires::Week => "1week",
+                PrivatebinExpires::Month => "1month",
+                PrivatebinExpires::Year => "1year",
+                PrivatebinExpires::Never => "never",
+            }
+        )
+    }
+}
+
+impl FromStr for PrivatebinExpires {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "5min" => Ok(PrivatebinExpires::FiveMinutes),
+            "10min" => Ok(PrivatebinExpires::TenMinutes),
+            "1hour" => Ok(PrivatebinExpires::Hour),
+            "1day" => Ok(PrivatebinExpires::Day),
+            "1week" => Ok(PrivatebinExpires::Week),
+            "1month" => Ok(PrivatebinExpires::Month),
+            "1year" => Ok(PrivatebinExpires::Year),
+            "never" => Ok(PrivatebinExpires::Never),
+            _ => Err(format!(
+                "{} (expected 5min, 10min, 1hour, 1day, 1week, 1month, 1year or never)",
+                s
+            )),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, Serialize, Copy, Clone, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum PrivatebinFormat {
+    Plaintext,
+    Syntaxhighlighting,
+    Markdown,
+}
+
+impl Display for PrivatebinFormat {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                PrivatebinFormat::Plaintext => "plaintext",
+                PrivatebinFormat::Syntaxhighlighting => "syntaxhighlighting",
+                PrivatebinFormat::Markdown => "markdown",
+            }
+        )
+    }
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone)]
+#[serde(deny_unknown_fields)]
+#[serde(rename_all = "snake_case")]
+pub struct Backend {
+    #[serde(with = "serde_url")]
+    pub url: Url,
+    pub expires: Option<PrivatebinExpires>,
+    #[serde(default)]
+    pub burn_after_reading: bool,
+    #[serde(default)]
+    pub open_discussion: bool,
+    pub password: Option<String>,
+    pub format: Option<PrivatebinFormat>,
+}
+
+#[derive(Debug, StructOpt)]
+#[structopt(about = "privatebin backend")]
+#[structopt(template = "{about}\n\nUSAGE:\n    {usage}\n\n{all-args}")]
+pub struct Opt {
+    /// Overrides url set in config
+    #[structopt(short = "u", long = "url")]
+    url: Option<Url>,
+    /// Time to live
+    #[structopt(
+        short = "e",
+        long = "expires",
+        value_name = "5min|10min|1hour|1day|1week|1month|1year|never|NONE"
+    )]
+    expires: Option<String>,
+    /// Delete the paste after it is first read
+    #[structopt(short = "b", long = "burn")]
+    burn: bool,
+    /// Allow comments on the paste
+    #[structopt(short = "d", long = "discussion")]
+    discussion: bool,
+    /// Password needed to decrypt the paste, besides the key in the url
+    #[structopt(short = "P", long = "password", value_name = "password|NONE")]
+    password: Option<String>,
+    /// How the paste is displayed
+    #[structopt(
+        short = "f",
+        long = "format",
+        value_name = "plaintext|syntaxhighlighting|markdown|NONE"
+    )]
+    format: Option<String>,
+}
+
+pub const NAME: &str = "privatebin";
+
+pub const INFO: &str = r#"PrivateBin backend.
+Supports servers running <https://privatebin.info/> (version 1.3 or newer).
+Pastes are encrypted before they are uploaded, so the server can't read them. The key is kept in
+the fragment of the paste url (the part after `#`), which browsers don't send to the server.
+The api only accepts text, so binary (non utf-8) input is rejected.
+
+Example config block:
+
+    [servers.privatebin]
+    backend = "privatebin"
+    url = "https://privatebin.net/"
+
+    # Optional values
+
+    # Time to live: one of 5min, 10min, 1hour, 1day, 1week, 1month, 1year or never. Default is
+    # set by the server.
+    expires = "1week"
+
+    # Delete the paste after it is first read. Default false.
+    burn_after_reading = true
+
+    # Allow comments on the paste. Can't be used with burn_after_reading. Default false.
+    open_discussion = false
+
+    # Password needed to decrypt the paste, as well as the key in the url. Default none.
+    password = "password123"
+
+    # How the paste is displayed: plaintext, syntaxhighlighting or markdown. Default plaintext.
+    format = "syntaxhighlighting"
+"#;
+
+pub const CAPABILITIES: Capabilities = Capabilities {
+    password: true,
+    expiry: ExpiryKind::Choice,
+    delete: true,
+    fetch: true,
+    ..Capabilities::NONE
+};
+
+/// PBKDF2 iterations and key size, as used by the PrivateBin web client.
+const KDF_ITERATIONS: usize = 100_000;
+const KEY_BITS: usize = 256;
+const TAG_BITS: usize = 128;
+
+impl PasteClient for Backend {
+    fn apply_args(&mut self, args: Vec<String>) -> clap::Result<()> {
+        let opt = Opt::from_iter_safe(args)?;
+        override_if_present(&mut self.url, opt.url);
+        override_option_with_option_none(&mut self.password, opt.password);
+        if opt.burn {
+            self.burn_after_reading = true;
+        }
+        if opt.discussion {
+            self.open_discussion = true;
+        }
+        if let Some(expires) = opt.expires {
+            self.expires = match expires.as_str() {
+                "NONE" => None,
+                _ => Some(expires.parse().map_err(|e| clap::Error {
+                    message: format!("Invalid value for expires: {}", e),
+                    kind: clap::ErrorKind::InvalidValue,
+                    info: None,
+                })?),
+            };
+        }
+        if let Some(format) = opt.format {
+            self.format = match format.as_str() {
+                "NONE" => None,
+                "plaintext" => Some(PrivatebinFormat::Plaintext),
+                "syntaxhighlighting" => Some(PrivatebinFormat::Syntaxhighlighting),
+                "markdown" => Some(PrivatebinFormat::Markdown),
+                _ => {
+                    return Err(clap::Error {
+                        message: format!("Invalid value for format: {}", format),
+                        kind: clap::ErrorKind::InvalidValue,
+                        info: None,
+                    });
+                }
+            };
+        }
+        Ok(())
+    }
+
+    fn paste(&self, client_config: &ClientConfig, data: PasteData) -> PasteResult<PasteInfo> {
+        if self.burn_after_reading && self.open_discussion {
+            return Err("burn_after_reading and open_discussion can't both be set"
+                .to_owned()
+                .into());
+        }
+        let text = bytes_to_text(data.into_bytes()?, NAME)?;
+
+        let mut key = [0; 32];
+        rand_bytes(&mut key)?;
+        let format = self.format.unwrap_or(PrivatebinFormat::Plaintext);
+        let adata = json!([
+            Value::Null,
+            format.to_string(),
+            self.open_discussion as u8,
+            self.burn_after_reading as u8
+        ]);
+        let (adata, ct) = encrypt(
+            &json!({ "paste": text }).to_string(),
+            &key,
+            self.password.as_deref(),
+            adata,
+        )?;
+
+        let mut params = json!({ "v": 2, "adata": adata, "ct": ct, "meta": {} });
+        if let Some(expires) = self.expires {
+            params["meta"]["expire"] = json!(expires.to_string());
+        }
+
+        let res: PasteResponse = client_config
+            .build()?
+            .post(self.url.clone())
+            .header(
+                "X-Requested-With",
+                HeaderValue::from_static("JSONHttpRequest"),
+            )
+            .json(&params)
+            .send()?
+            .check_status()?
+            .json()?;
+        if res.status != 0 {
+            return Err(format!("api returned failure: {:?}", res.message).into());
+        }
+        let id = match res.id {
+            Some(id) => id,
+            None => return Err("no paste id returned in response".to_owned().into()),
+        };
+
+        let mut url = self.url.clone();
+        url.set_query(Some(&id));
+        // the key never goes to the server; it lives in the fragment of the url
+        url.set_fragment(Some(&bs58::encode(key).into_string()));
+        let mut info = PasteInfo::from(url);
+        if let Some(token) = res.deletetoken {
+            let mut manage_url = self.url.clone();
+            manage_url
+                .query_pairs_mut()
+                .append_pair("pasteid", &id)
+                .append_pair("deletetoken", &token);
+            info.manage_url = Some(manage_url);
+            info.delete_token = Some(token);
+        }
+        info.expires = self
+            .expires
+            .and_then(|expires| expires.duration())
+            .map(|duration| SystemTime::now() + duration);
+        info.password_required = self.password.is_some();
+        if self.burn_after_reading {
+            info.extras
+                .insert("burn_after_reading".to_owned(), "true".to_owned());
+        }
+        Ok(info)
+    }
+
+    fn host(&self) -> Option<String> {
+        self.url.host_str().map(|h| h.to_owned())
+    }
+
+    fn fetch(&self, client_config: &ClientConfig, url: &Url) -> PasteResult<Vec<u8>> {
+        let (id, key) = paste_id_and_key(url)?;
+        let mut api_url = self.url.clone();
+        api_url.set_query(Some(&format!("pasteid={}", id)));
+
+        let res: PasteResponse = client_config
+            .build()?
+            .get(api_url)
+            .header(
+                "X-Requested-With",
+                HeaderValue::from_static("JSONHttpRequest"),
+            )
+            .send()?
+            .check_status()?
+            .json()?;
+        if res.status != 0 {
+            return Err(format!("api returned failure: {:?}", res.message).into());
+        }
+        let (adata, ct) = match (res.adata, res.ct) {
+            (Some(adata), Some(ct)) => (adata, ct),
+            _ => {
+                return Err("paste is not in the PrivateBin v2 format".to_owned().into());
+            }
+        };
+
+        let plaintext = decrypt(&adata, &ct, &key, self.password.as_deref())?;
+        let content: PasteContent = serde_json::from_slice(&plaintext)
+            .map_err(|e| format!("could not read decrypted paste: {}", e))?;
+        Ok(content.paste.into_bytes())
+    }
+
+    fn delete(
+        &self,
+        client_config: &ClientConfig,
+        url: &Url,
+        token: Option<&str>,
+    ) -> PasteResult<()> {
+        let token = match token {
+            Some(token) => token,
+            None => {
+                return Err("a delete token is required to delete a paste"
+                    .to_owned()
+                    .into());
+            }
+        };
+        let (id, _) = paste_id_and_key(url)?;
+        let mut api_url = self.url.clone();
+        api_url
+            .query_pairs_mut()
+            .append_pair("pasteid", &id)
+            .append_pair("deletetoken", token);
+
+        let res: PasteResponse = client_config
+            .build()?
+            .get(api_url)
+            .header(
+                "X-Requested-With",
+                HeaderValue::from_static("JSONHttpRequest"),
+            )
+            .send()?
+            .check_status()?
+            .json()?;
+        if res.status != 0 {
+            return Err(format!("api returned failure: {:?}", res.message).into());
+        }
+        Ok(())
+    }
+}
+
+/// Paste urls are of the form <server>?<paste id>#<base58 key>.
+fn paste_id_and_key(url: &Url) -> PasteResult<(String, Vec<u8>)> {
+    let id = match url.query() {
+        Some(query) if !query.is_empty() && !query.contains('=') => query.to_owned(),
+        _ => return Err(format!("could not find paste id in url: {}", url).into()),
+    };
+    let key = match url.fragment() {
+        // the web client may add a `-` to the fragment to load the paste without asking first
+        Some(fragment) => bs58::decode(fragment.trim_start_matches('-'))
+            .into_vec()
+            .map_err(|e| format!("invalid key in url {}: {}", url, e))?,
+        None => return Err(format!("no key in url (the part after #): {}", url).into()),
+    };
+    Ok((id, key))
+}
+
+/// Derive the AES key from the random key in the url, and the password if there is one.
+fn derive_key(
+    key: &[u8],
+    password: Option<&str>,
+    salt: &[u8],
+    iterations: usize,
+) -> PasteResult<Vec<u8>> {
+    let mut material = key.to_vec();
+    if let Some(password) = password {
+        material.extend_from_slice(password.as_bytes());
+    }
+    let mut derived = vec![0; KEY_BITS / 8];
+    pbkdf2_hmac(
+        &material,
+        salt,
+        iterations,
+        MessageDigest::sha256(),
+        &mut derived,
+    )?;
+    Ok(derived)
+}
+
+/// Encrypt `plaintext` in the PrivateBin v2 format. The cipher spec is filled in as the first
+/// element of `adata`, which is authenticated along with the ciphertext. Returns the completed
+/// adata and the base64 ciphertext.
+fn encrypt(
+    plaintext: &str,
+    key: &[u8],
+    password: Option<&str>,
+    mut adata: Value,
+) -> PasteResult<(Value, String)> {
+    let mut iv = [0; 16];
+    let mut salt = [0; 8];
+    rand_bytes(&mut iv)?;
+    rand_bytes(&mut salt)?;
+
+    let mut compressed = DeflateEncoder::new(Vec::new(), Compression::default());
+    compressed.write_all(plaintext.as_bytes())?;
+    let compressed = compressed.finish()?;
+
+    adata[0] = json!([
+        base64::encode(&iv),
+        base64::encode(&salt),
+        KDF_ITERATIONS,
+        KEY_BITS,
+        TAG_BITS,
+        "aes",
+        "gcm",
+        "zlib"
+    ]);
+    let aes_key = derive_key(key, password, &salt, KDF_ITERATIONS)?;
+    let mut tag = [0; TAG_BITS / 8];
+    let mut ct = encrypt_aead(
+        Cipher::aes_256_gcm(),
+        &aes_key,
+        Some(&iv),
+        adata.to_string().as_bytes(),
+        &compressed,
+        &mut tag,
+    )?;
+    ct.extend_from_slice(&tag);
+    Ok((adata, base64::encode(&ct)))
+}
+
+/// Decrypt a paste in the PrivateBin v2 format.
+fn decrypt(adata: &Value, ct: &str, key: &[u8], password: Option<&str>) -> PasteResult<Vec<u8>> {
+    let spec: CipherSpec = serde_json::from_value(adata[0].clone())
+        .map_err(|e| format!("unsupported cipher spec: {}", e))?;
+    if spec.5 != "aes" || spec.6 != "gcm" || spec.3 != KEY_BITS || spec.4 != TAG_BITS {
+        return Err(format!("unsupported cipher: {}-{}-{}", spec.5, spec.3, spec.6).into());
+    }
+    let iv = base64::decode(&spec.0).map_err(|e| format!("invalid iv: {}", e))?;
+    let salt = base64::decode(&spec.1).map_err(|e| format!("invalid salt: {}", e))?;
+    let ct = base64::decode(ct).map_err(|e| format!("invalid ciphertext: {}", e))?;
+    if ct.len() < TAG_BITS / 8 {
+        return Err("ciphertext is too short".to_owned().into());
+    }
+    let (ct, tag) = ct.split_at(ct.len() - TAG_BITS / 8);
+
+    let aes_key = derive_key(key, password, &salt, spec.2)?;
+    let compressed = decrypt_aead(
+        Cipher::aes_256_gcm(),
+        &aes_key,
+        Some(&iv),
+        adata.to_string().as_bytes(),
+        ct,
+        tag,
+    )
+    .map_err(|_| "could not decrypt paste: wrong key or password".to_owned())?;
+
+    match spec.7.as_str() {
+        "none" => Ok(compressed),
+        "zlib" => {
+            let mut plaintext = vec![];
+            DeflateDecoder::new(&compressed[..]).read_to_end(&mut plaintext)?;
+            Ok(plaintext)
+        }
+        other => Err(format!("unsupported compression: {}", other).into()),
+    }
+}
+
+/// iv, salt, iterations, key size, tag size, algorithm, mode, compression.
+#[derive(Debug, Deserialize)]
+struct CipherSpec(String, String, usize, usize, usize, String, String, String);
+
+#[derive(Debug, Deserialize, Serialize)]
+struct PasteContent {
+    paste: String,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+struct PasteResponse {
+    status: u8,
+    message: Option<String>,
+    id: Option<String>,
+    deletetoken: Option<String>,
+    adata: Option<Value>,
+    ct: Option<String>,
+}
+
+impl Display for Backend {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(f, "privatebin | {}", self.url)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // made with python's cryptography package: pbkdf2-sha256 over the key (and password), raw
+    // deflate, and aes-256-gcm with the compact json of the adata as aad
+    const URL: &str =
+        "https://privatebin.example/?f468483c313401e8#1thX6LZfHDZZKUs92febYZhYRcXddmzfzF2NvTkPNE";
+    const ADATA: &str = r#"[["ZGVmZ2hpamtsbW5vcHFycw==","yMnKy8zNzs8=",100000,256,128,"aes","gcm","zlib"],"plaintext",0,0]"#;
+    const CT: &str = "JMfd4lvLO4SZl9Pzwg9mGsIi+fyJ2L/mWa3WRVnH8afvGHeKXqpVN0rXmn4k";
+    const CT_WITH_PASSWORD: &str = "jws4PFGVhsvWlLLRZigdomM+s7ir16Fi7zD4vBbtM9RNJpRQQ5DlDsqASAKC";
+    const PLAINTEXT: &str = "hello from pc\n";
+
+    fn paste_text(plaintext: &[u8]) -> String {
+        serde_json::from_slice::<PasteContent>(plaintext)
+            .unwrap()
+            .paste
+    }
+
+    #[test]
+    fn url_key_is_base58() {
+        let (id, key) = paste_id_and_key(&Url::parse(URL).unwrap()).unwrap();
+        assert_eq!(id, "f468483c313401e8");
+        assert_eq!(key, (0..32).collect::<Vec<u8>>());
+
+        // the web client's prefix for loading without asking
+        let url = Url::parse(&URL.replace('#', "#-")).unwrap();
+        assert_eq!(paste_id_and_key(&url).unwrap().1, key);
+    }
+
+    #[test]
+    fn url_needs_id_and_key() {
+        for url in &[
+            "https://privatebin.example/#1thX6LZfHDZZKUs92febYZhYRcXddmzfzF2NvTkPNE",
+            "https://privatebin.example/?pasteid=f468483c313401e8#1thX6LZfHDZZKUs92febYZhY",
+            "https://privatebin.example/?f468483c313401e8",
+            "https://privatebin.example/?f468483c313401e8#0OIl",
+        ] {
+            assert!(
+                paste_id_and_key(&Url::parse(url).unwrap()).is_err(),
+                "{}",
+                url
+            );
+        }
+    }
+
+    #[test]
+    fn decrypt_known_vector() {
+        let (_, key) = paste_id_and_key(&Url::parse(URL).unwrap()).unwrap();
+        let adata: Value = serde_json::from_str(ADATA).unwrap();
+        let plaintext = decrypt(&adata, CT, &key, None).unwrap();
+        assert_eq!(paste_text(&plaintext), PLAINTEXT);
+    }
+
+    #[test]
+    fn decrypt_known_vector_with_password() {
+        let (_, key) = paste_id_and_key(&Url::parse(URL).unwrap()).unwrap();
+        let adata: Value = serde_json::from_str(ADATA).unwrap();
+        let plaintext = decrypt(&adata, CT_WITH_PASSWORD, &key, Some("hunter2")).unwrap();
+        assert_eq!(paste_text(&plaintext), PLAINTEXT);
+
+        assert!(decrypt(&adata, CT_WITH_PASSWORD, &key, None).is_err());
+        assert!(decrypt(&adata, CT_WITH_PASSWORD, &key, Some("hunter3")).is_err());
+    }
+
+    #[test]
+    fn adata_is_authenticated() {
+        let (_, key) = paste_id_and_key(&Url::parse(URL).unwrap()).unwrap();
+        let mut adata: Value = serde_json::from_str(ADATA).unwrap();
+        adata[3] = json!(1);
+        assert!(decrypt(&adata, CT, &key, None).is_err());
+    }
+
+    #[test]
+    fn decrypt_rejects_other_ciphers() {
+        let (_, key) = paste_id_and_key(&Url::parse(URL).unwrap()).unwrap();
+        let adata: Value = serde_json::from_str(&ADATA.replace("gcm", "ccm")).unwrap();
+        assert!(decrypt(&adata, CT, &key, None).is_err());
+    }
+
+    #[test]
+    fn encrypt_round_trip() {
+        let key = [42; 32];
+        let adata = json!([Value::Null, "markdown", 1, 0]);
+        let content = json!({ "paste": PLAINTEXT }).to_string();
+        let (adata, ct) = encrypt(&content, &key, Some("hunter2"), adata).unwrap();
+
+        let spec: CipherSpec = serde_json::from_value(adata[0].clone()).unwrap();
+        assert_eq!(base64::decode(&spec.0).unwrap().len(), 16);
+        assert_eq!(base64::decode(&spec.1).unwrap().len(), 8);
+        assert_eq!(
+            (spec.2, spec.3, spec.4),
+            (KDF_ITERATIONS, KEY_BITS, TAG_BITS)
+        );
+        assert_eq!((&*spec.5, &*spec.6, &*spec.7), ("aes", "gcm", "zlib"));
+        assert_eq!(adata[1], "markdown");
+        assert_eq!(adata[2], 1);
+
+        let plaintext = decrypt(&adata, &ct, &key, Some("hunter2")).unwrap();
+        assert_eq!(paste_text(&plaintext), PLAINTEXT);
+        assert!(decrypt(&adata, &ct, &[43; 32], Some("hunter2")).is_err());
+    }
+}
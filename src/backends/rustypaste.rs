@@ -0,0 +1,273 @@
+use std::fmt::{self, Display, Formatter};
+use std::time::{Duration, SystemTime};
+
+use reqwest::multipart::{Form, Part};
+use reqwest::{Method, RequestBuilder};
+use serde::{Deserialize, Serialize};
+use structopt::StructOpt;
+use url::Url;
+
+use crate::backends::{Capabilities, ExpiryKind};
+use crate::client::{ClientConfig, ResponseExt};
+use crate::error::PasteResult;
+use crate::types::{PasteClient, PasteData, PasteInfo};
+use crate::utils::{
+    bytes_to_text, override_if_present, override_option_duration_with_option_none,
+    override_option_with_option_none, serde_humantime, serde_url,
+};
+
+#[derive(Debug, Deserialize, Serialize, Clone)]
+#[serde(deny_unknown_fields)]
+#[serde(rename_all = "snake_case")]
+pub struct Backend {
+    #[serde(with = "serde_url")]
+    pub url: Url,
+    pub token: Option<String>,
+    pub delete_token: Option<String>,
+    #[serde(default)]
+    #[serde(with = "serde_humantime")]
+    pub expires: Option<Duration>,
+    #[serde(default)]
+    pub oneshot: bool,
+    #[serde(default)]
+    pub shorten: bool,
+    #[serde(default)]
+    pub remote: bool,
+    pub filename: Option<String>,
+}
+
+#[derive(Debug, StructOpt)]
+#[structopt(about = "rustypaste backend")]
+#[structopt(template = "{about}\n\nUSAGE:\n    {usage}\n\n{all-args}")]
+pub struct Opt {
+    /// Overrides url set in config
+    #[structopt(short = "u", long = "url")]
+    url: Option<Url>,
+    /// Auth token, if the server requires one
+    #[structopt(short = "k", long = "token", value_name = "token|NONE")]
+    token: Option<String>,
+    /// Token for deleting pastes, if the server allows it
+    #[structopt(short = "D", long = "delete-token", value_name = "token|NONE")]
+    delete_token: Option<String>,
+    /// Time to live as a duration
+    #[structopt(short = "e", long = "expires", value_name = "duration|NONE")]
+    expires: Option<String>,
+    /// Make the paste (or short url) work only once
+    #[structopt(short = "O", long = "oneshot")]
+    oneshot: bool,
+    /// Take the input as a url, and paste a short url redirecting to it
+    #[structopt(short = "U", long = "shorten", conflicts_with = "remote")]
+    shorten: bool,
+    /// Take the input as a url, and have the server upload the file found there
+    #[structopt(short = "r", long = "remote")]
+    remote: bool,
+    /// File name to upload as, which the server takes the url's extension from
+    #[structopt(short = "n", long = "filename", value_name = "filename|NONE")]
+    filename: Option<String>,
+}
+
+pub const NAME: &str = "rustypaste";
+
+pub const INFO: &str = r#"Rustypaste backend.
+Supports servers running rustypaste <https://github.com/orhun/rustypaste>. Any file can be
+uploaded, binary or text.
+
+Besides plain uploads, the input can be a url: with `--shorten` the paste is a short url that
+redirects to it, and with `--remote` the server uploads the file found there. `--oneshot` makes a
+paste or short url that can only be viewed once.
+
+Example config block:
+
+    [servers.rusty]
+    backend = "rustypaste"
+    url = "https://paste.example.com/"
+
+    # Optional values
+
+    # Auth token, for servers that require one.
+    token = "0f6b9a2c4e8d1f3a"
+
+    # Token for deleting pastes, for servers that allow it.
+    delete_token = "7c3e5a9b1d2f4e6a"
+
+    # Time to live as a duration. Default is set by the server (usually never).
+    expires = "1day"
+
+    # Make pastes that can only be viewed once. Default false.
+    oneshot = false
+
+    # Take the input as a url, and paste a short url for it. Default false.
+    shorten = false
+
+    # Take the input as a url, and upload the file found there. Default false.
+    remote = false
+
+    # File name to upload as; the url gets its extension. Default is the input file name, or
+    # "paste" for stdin.
+    filename = "debug.log"
+"#;
+
+pub const CAPABILITIES: Capabilities = Capabilities {
+    expiry: ExpiryKind::Duration,
+    auth: true,
+    delete: true,
+    fetch: true,
+    ..Capabilities::NONE
+};
+
+/// File name for content from stdin, when none is configured. The server rejects uploads without
+/// one.
+const DEFAULT_FILENAME: &str = "paste";
+
+impl PasteClient for Backend {
+    fn apply_args(&mut self, args: Vec<String>) -> clap::Result<()> {
+        let opt = Opt::from_iter_safe(args)?;
+        override_if_present(&mut self.url, opt.url);
+        override_option_with_option_none(&mut self.token, opt.token);
+        override_option_with_option_none(&mut self.delete_token, opt.delete_token);
+        override_option_duration_with_option_none(&mut self.expires, opt.expires)?;
+        override_option_with_option_none(&mut self.filename, opt.filename);
+        if opt.oneshot {
+            self.oneshot = true;
+        }
+        if opt.shorten {
+            self.shorten = true;
+            self.remote = false;
+        }
+        if opt.remote {
+            self.remote = true;
+            self.shorten = false;
+        }
+        // checked here too for settings from the config, so they fail before the input is read
+        if self.shorten && self.remote {
+            return Err(clap::Error {
+                message: "shorten and remote can't both be set".to_owned(),
+                kind: clap::ErrorKind::ArgumentConflict,
+                info: None,
+            });
+        }
+        if self.remote && self.oneshot {
+            return Err(clap::Error {
+                message: "remote uploads can't be oneshot".to_owned(),
+                kind: clap::ErrorKind::ArgumentConflict,
+                info: None,
+            });
+        }
+        Ok(())
+    }
+
+    fn paste(&self, client_config: &ClientConfig, data: PasteData) -> PasteResult<PasteInfo> {
+        // the field name says what the server should do with the upload
+        let form = if self.shorten || self.remote {
+            let field = match (self.remote, self.oneshot) {
+                (true, _) => "remote",
+                (false, true) => "oneshot_url",
+                (false, false) => "url",
+            };
+            Form::new().text(field, input_url(data)?.into_string())
+        } else {
+            let field = if self.oneshot { "oneshot" } else { "file" };
+            let filename = self.filename.as_deref().unwrap_or(DEFAULT_FILENAME);
+            Form::new().part(
+                field,
+                Part::bytes(data.into_bytes()?).file_name(filename.to_owned()),
+            )
+        };
+
+        let mut request = self
+            .request(
+                client_config,
+                Method::POST,
+                &self.url,
+                self.token.as_deref(),
+            )?
+            .multipart(form);
+        if let Some(expires) = self.expires {
+            request = request.header(
+                "expire",
+                humantime::format_duration(expires).to_string().as_str(),
+            );
+        }
+        let mut res = request.send()?.check_status()?;
+        let status = res.status();
+        let text = res.text()?;
+        if !status.is_success() {
+            return Err(format!("server returned failure: {} ({})", text.trim(), status).into());
+        }
+
+        let mut info = PasteInfo::from(Url::parse(text.trim())?);
+        // short urls redirect rather than serving content
+        if !self.shorten {
+            info.raw_url = Some(info.url.clone());
+        }
+        info.expires = self.expires.map(|expires| SystemTime::now() + expires);
+        Ok(info)
+    }
+
+    fn default_file_name(&mut self, name: &str) {
+        if self.filename.is_none() {
+            self.filename = Some(name.to_owned());
+        }
+    }
+
+    fn host(&self) -> Option<String> {
+        self.url.host_str().map(|h| h.to_owned())
+    }
+
+    fn fetch(&self, client_config: &ClientConfig, url: &Url) -> PasteResult<Vec<u8>> {
+        self.request(client_config, Method::GET, url, self.token.as_deref())?
+            .send()?
+            .read_body()
+    }
+
+    fn delete(
+        &self,
+        client_config: &ClientConfig,
+        url: &Url,
+        token: Option<&str>,
+    ) -> PasteResult<()> {
+        let token = match token.or(self.delete_token.as_deref()) {
+            Some(token) => token,
+            None => {
+                return Err("deleting a paste needs a delete token: set delete_token"
+                    .to_owned()
+                    .into());
+            }
+        };
+        self.request(client_config, Method::DELETE, url, Some(token))?
+            .send()?
+            .check_status()?
+            .error_for_status()?;
+        Ok(())
+    }
+}
+
+impl Backend {
+    /// Start a request, sending `token` in the Authorization header.
+    fn request(
+        &self,
+        client_config: &ClientConfig,
+        method: Method,
+        url: &Url,
+        token: Option<&str>,
+    ) -> PasteResult<RequestBuilder> {
+        let request = client_config.build()?.request(method, url.clone());
+        Ok(match token {
+            Some(token) => request.header("Authorization", token),
+            None => request,
+        })
+    }
+}
+
+/// The url given as input, for shortening or remote uploads.
+fn input_url(data: PasteData) -> PasteResult<Url> {
+    let text = bytes_to_text(data.into_bytes()?, NAME)?;
+    Url::parse(text.trim())
+        .map_err(|e| format!("the input must be a url to shorten or upload ({})", e).into())
+}
+
+impl Display for Backend {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(f, "rustypaste | {}", self.url)
+    }
+}
@@ -1,14 +1,16 @@
 use std::fmt::{self, Display, Formatter};
 
-use reqwest::multipart::Form;
-use reqwest::Client;
+use reqwest::multipart::{Form, Part};
 use serde::{Deserialize, Serialize};
 use structopt::StructOpt;
 use url::Url;
 
+use crate::backends::{Capabilities, SyntaxKind};
+use crate::client::{ClientConfig, ResponseExt};
 use crate::error::PasteResult;
-use crate::types::PasteClient;
-use crate::utils::{override_if_present, override_option_with_option_none, serde_url};
+use crate::syntax;
+use crate::types::{PasteClient, PasteData, PasteInfo};
+use crate::utils::{override_if_present, override_syntax, serde_url};
 
 #[derive(Debug, Deserialize, Serialize, Clone)]
 #[serde(deny_unknown_fields)]
@@ -50,26 +52,65 @@ Example config block:
     syntax = "py"
 "#;
 
+pub const CAPABILITIES: Capabilities = Capabilities {
+    syntax: SyntaxKind::Given,
+    fetch: true,
+    ..Capabilities::NONE
+};
+
+pub const SYNTAXES: &[(&str, &str)] = syntax::PYGMENTS;
+
 impl PasteClient for Backend {
     fn apply_args(&mut self, args: Vec<String>) -> clap::Result<()> {
         let opt = Opt::from_iter_safe(args)?;
         override_if_present(&mut self.url, opt.url);
-        override_option_with_option_none(&mut self.syntax, opt.syntax);
+        override_syntax(&mut self.syntax, opt.syntax);
         Ok(())
     }
 
-    fn paste(&self, data: String) -> PasteResult<Url> {
-        let form = Form::new().text("sprunge", data);
-        let text = Client::new()
+    fn paste(&self, client_config: &ClientConfig, data: PasteData) -> PasteResult<PasteInfo> {
+        let form = Form::new().part("sprunge", Part::bytes(data.into_bytes()?));
+        let text = client_config
+            .build()?
             .post(self.url.clone())
             .multipart(form)
             .send()?
+            .check_status()?
             .text()?;
-        let mut url = Url::parse(&text)?;
-        if let Some(ref lang) = self.syntax {
+        let raw_url = Url::parse(&text)?;
+        let mut url = raw_url.clone();
+        if let Some(lang) = syntax::to_send(&self.syntax) {
             url.set_query(Some(lang));
         }
-        Ok(url)
+        let mut info = PasteInfo::from(url);
+        info.raw_url = Some(raw_url);
+        Ok(info)
+    }
+
+    fn default_syntax(&mut self, syntax: &str) {
+        if self.syntax.is_none() {
+            self.syntax = Some(syntax.to_owned());
+        }
+    }
+
+    fn set_syntax(&mut self, syntax: Option<&str>) {
+        self.syntax = syntax.map(|s| s.to_owned());
+    }
+
+    fn syntax(&self) -> Option<&str> {
+        self.syntax.as_deref()
+    }
+
+    fn host(&self) -> Option<String> {
+        self.url.host_str().map(|h| h.to_owned())
+    }
+
+    fn fetch(&self, client_config: &ClientConfig, url: &Url) -> PasteResult<Vec<u8>> {
+        // the query string only enables html syntax highlighting
+        let mut raw_url = url.clone();
+        raw_url.set_query(None);
+
+        client_config.build()?.get(raw_url).send()?.read_body()
     }
 }
 
@@ -1,14 +1,19 @@
 use std::fmt::{self, Display, Formatter};
+use std::time::{Duration, SystemTime};
 
-use reqwest::multipart::Form;
-use reqwest::Client;
+use reqwest::multipart::{Form, Part};
 use serde::{Deserialize, Serialize};
 use structopt::StructOpt;
 use url::Url;
 
+use crate::backends::{Capabilities, ExpiryKind, SyntaxKind};
+use crate::client::{ClientConfig, ResponseExt};
 use crate::error::PasteResult;
-use crate::types::PasteClient;
-use crate::utils::{override_if_present, override_option_with_option_none, serde_url};
+use crate::syntax;
+use crate::types::{PasteClient, PasteData, PasteInfo};
+use crate::utils::{
+    override_if_present, override_option_with_option_none, override_syntax, serde_url,
+};
 
 #[derive(Debug, Deserialize, Serialize, Copy, Clone)]
 #[serde(rename_all = "snake_case")]
@@ -19,6 +24,19 @@ pub enum UbuntuExpires {
     Year,
 }
 
+impl UbuntuExpires {
+    /// Roughly how long a paste is kept for.
+    fn duration(&self) -> Duration {
+        let days = match self {
+            UbuntuExpires::Day => 1,
+            UbuntuExpires::Week => 7,
+            UbuntuExpires::Month => 30,
+            UbuntuExpires::Year => 365,
+        };
+        Duration::from_secs(days * 24 * 60 * 60)
+    }
+}
+
 impl Display for UbuntuExpires {
     fn fmt(&self, f: &mut Formatter) -> fmt::Result {
         write!(
@@ -88,11 +106,20 @@ Example config block:
     author = "my name"
 "#;
 
+pub const CAPABILITIES: Capabilities = Capabilities {
+    author: true,
+    syntax: SyntaxKind::Given,
+    expiry: ExpiryKind::Choice,
+    ..Capabilities::NONE
+};
+
+pub const SYNTAXES: &[(&str, &str)] = syntax::PYGMENTS;
+
 impl PasteClient for Backend {
     fn apply_args(&mut self, args: Vec<String>) -> clap::Result<()> {
         let opt = Opt::from_iter_safe(args)?;
         override_if_present(&mut self.url, opt.url);
-        override_option_with_option_none(&mut self.syntax, opt.syntax);
+        override_syntax(&mut self.syntax, opt.syntax);
         override_option_with_option_none(&mut self.author, opt.author);
         if let Some(ref expires) = opt.expires {
             match expires.as_str() {
@@ -123,11 +150,11 @@ impl PasteClient for Backend {
         Ok(())
     }
 
-    fn paste(&self, data: String) -> PasteResult<Url> {
-        let form = Form::new().text("content", data);
-        let form = match self.syntax {
+    fn paste(&self, client_config: &ClientConfig, data: PasteData) -> PasteResult<PasteInfo> {
+        let form = Form::new().part("content", Part::bytes(data.into_bytes()?));
+        let form = match syntax::to_send(&self.syntax) {
             None => form.text("syntax", "text".to_owned()),
-            Some(ref syntax) => form.text("syntax", syntax.to_owned()),
+            Some(syntax) => form.text("syntax", syntax.to_owned()),
         };
         let form = match self.author {
             Some(ref author) => form.text("poster", author.to_owned()),
@@ -138,19 +165,51 @@ impl PasteClient for Backend {
             None => form,
         };
 
-        let res = Client::new()
+        let res = client_config
+            .build()?
             .post(self.url.clone())
             .multipart(form)
-            .send()?;
+            .send()?
+            .check_status()?;
 
         // Fails silently in some cases, for example if the syntax wasn't recognized. In this case
         // it redirects back to the main url. Web users will see a helpful message in the form.
         if res.url() == &self.url {
             Err("Paste failed.\nCheck parameters, it is possible that the syntax name provided wasn't recognized.".to_owned().into())
         } else {
-            Ok(res.url().to_owned())
+            let mut info = PasteInfo::from(res.url().to_owned());
+            let mut raw_url = info.url.clone();
+            raw_url
+                .path_segments_mut()
+                .map_err(|_| format!("unexpected paste url: {}", info.url))?
+                .pop_if_empty()
+                .extend(&["plain", ""]);
+            info.raw_url = Some(raw_url);
+            info.expires = self
+                .expires
+                .as_ref()
+                .map(|expires| SystemTime::now() + expires.duration());
+            Ok(info)
         }
     }
+
+    fn default_syntax(&mut self, syntax: &str) {
+        if self.syntax.is_none() {
+            self.syntax = Some(syntax.to_owned());
+        }
+    }
+
+    fn set_syntax(&mut self, syntax: Option<&str>) {
+        self.syntax = syntax.map(|s| s.to_owned());
+    }
+
+    fn syntax(&self) -> Option<&str> {
+        self.syntax.as_deref()
+    }
+
+    fn host(&self) -> Option<String> {
+        self.url.host_str().map(|h| h.to_owned())
+    }
 }
 
 impl Display for Backend {
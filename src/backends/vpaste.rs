@@ -1,13 +1,14 @@
 use std::fmt::{self, Display, Formatter};
 
-use reqwest::multipart::Form;
-use reqwest::Client;
+use reqwest::multipart::{Form, Part};
 use serde::{Deserialize, Serialize};
 use structopt::StructOpt;
 use url::Url;
 
+use crate::backends::Capabilities;
+use crate::client::{ClientConfig, ResponseExt};
 use crate::error::PasteResult;
-use crate::types::PasteClient;
+use crate::types::{PasteClient, PasteData, PasteInfo};
 use crate::utils::{override_if_present, serde_url};
 
 #[derive(Debug, Deserialize, Serialize, Clone)]
@@ -39,6 +40,10 @@ Example config block:
     url = "http://vpaste.net/"
 "#;
 
+pub const CAPABILITIES: Capabilities = Capabilities {
+    ..Capabilities::NONE
+};
+
 impl PasteClient for Backend {
     fn apply_args(&mut self, args: Vec<String>) -> clap::Result<()> {
         let opt = Opt::from_iter_safe(args)?;
@@ -46,13 +51,19 @@ impl PasteClient for Backend {
         Ok(())
     }
 
-    fn paste(&self, data: String) -> PasteResult<Url> {
-        let form = Form::new().text("text", data);
-        let res = Client::new()
+    fn paste(&self, client_config: &ClientConfig, data: PasteData) -> PasteResult<PasteInfo> {
+        let form = Form::new().part("text", Part::bytes(data.into_bytes()?));
+        let res = client_config
+            .build()?
             .post(self.url.clone())
             .multipart(form)
-            .send()?;
-        Ok(res.url().to_owned())
+            .send()?
+            .check_status()?;
+        Ok(res.url().to_owned().into())
+    }
+
+    fn host(&self) -> Option<String> {
+        self.url.host_str().map(|h| h.to_owned())
     }
 }
 
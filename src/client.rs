@@ -0,0 +1,607 @@
+use std::env;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::net::{TcpStream, ToSocketAddrs};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+use openssl::pkcs12::Pkcs12;
+use openssl::pkey::PKey;
+use openssl::stack::Stack;
+use openssl::x509::X509;
+use reqwest::header::{HeaderValue, RETRY_AFTER};
+use reqwest::{Certificate, Client, Identity, Method, Proxy, RequestBuilder, Response, StatusCode};
+use serde::Deserialize;
+use serde_json::Value;
+use socks::Socks5Stream;
+use url::Url;
+
+use crate::error::{PasteError, PasteResult};
+
+/// Network settings for a server, resolved from the server block and the main config. Backends
+/// create their http clients and tcp connections through this, rather than directly.
+#[derive(Debug, Clone, Default)]
+pub struct ClientConfig {
+    /// Timeout for establishing a connection. Default is no timeout.
+    pub connect_timeout: Option<Duration>,
+    /// Timeout for reading and writing on a connection. Default is the reqwest default (30s) for
+    /// http, and no timeout for raw tcp.
+    pub timeout: Option<Duration>,
+    /// Proxy url to connect through; http, https, socks5 and socks5h schemes are supported.
+    pub proxy: Option<String>,
+    /// Hosts to connect to directly, bypassing the proxy. An entry matches the host itself and
+    /// all its subdomains; "*" matches every host.
+    pub no_proxy: Vec<String>,
+    /// PEM file of extra CA certificates to trust, in addition to the system trust store.
+    pub ca_file: Option<PathBuf>,
+    /// PEM client certificate (optionally followed by its chain) to present to the server.
+    pub client_cert: Option<PathBuf>,
+    /// PEM private key for `client_cert`. Default is to read the key from `client_cert`.
+    pub client_key: Option<PathBuf>,
+    /// Accept any server certificate and hostname. Only meant for lab servers.
+    pub insecure_skip_verify: bool,
+}
+
+impl ClientConfig {
+    /// Build a http client using these settings.
+    pub fn build(&self) -> PasteResult<Client> {
+        let builder = Client::builder().connect_timeout(self.connect_timeout);
+        let builder = match self.timeout {
+            Some(timeout) => builder.timeout(timeout),
+            None => builder,
+        };
+        let builder = match self.proxy_url()? {
+            Some(proxy_url) => {
+                let no_proxy = self.no_proxy.clone();
+                builder.proxy(Proxy::custom(move |url| match url.host_str() {
+                    Some(host) if bypasses_proxy(&no_proxy, host) => None,
+                    _ => Some(proxy_url.clone()),
+                }))
+            }
+            None => builder,
+        };
+        let builder = match self.ca_file {
+            Some(ref ca_file) => read_ca_file(ca_file)?
+                .into_iter()
+                .fold(builder, |builder, cert| builder.add_root_certificate(cert)),
+            None => builder,
+        };
+        let builder = match self.client_cert {
+            Some(ref client_cert) => {
+                let client_key = self.client_key.as_ref().unwrap_or(client_cert);
+                builder.identity(read_identity(client_cert, client_key)?)
+            }
+            None => builder,
+        };
+        let builder = builder
+            .danger_accept_invalid_certs(self.insecure_skip_verify)
+            .danger_accept_invalid_hostnames(self.insecure_skip_verify);
+
+        Ok(builder.build()?)
+    }
+
+    /// Open a raw tcp connection using these settings. Only socks proxies can be used for this.
+    pub fn connect_tcp(&self, domain: &str, port: u16) -> PasteResult<TcpStream> {
+        let stream = match self.proxy_url()? {
+            Some(ref proxy_url) if !bypasses_proxy(&self.no_proxy, domain) => {
+                connect_socks(proxy_url, domain, port)?
+            }
+            _ => self.connect_direct(domain, port)?,
+        };
+        stream.set_read_timeout(self.timeout)?;
+        stream.set_write_timeout(self.timeout)?;
+        Ok(stream)
+    }
+
+    fn proxy_url(&self) -> PasteResult<Option<Url>> {
+        let proxy = match self.proxy {
+            Some(ref proxy) => proxy,
+            None => return Ok(None),
+        };
+
+        let proxy_url = Url::parse(proxy)?;
+        match proxy_url.scheme() {
+            "http" | "https" | "socks5" | "socks5h" => Ok(Some(proxy_url)),
+            scheme => Err(format!(
+                "unsupported proxy scheme {:?}; expected one of http, https, socks5, socks5h",
+                scheme
+            )
+            .into()),
+        }
+    }
+
+    fn connect_direct(&self, domain: &str, port: u16) -> PasteResult<TcpStream> {
+        let stream = match self.connect_timeout {
+            None => TcpStream::connect((domain, port))?,
+            Some(timeout) => {
+                // connect_timeout only takes a single address, so try each in turn
+                let mut last_err = None;
+                let mut stream = None;
+                for addr in (domain, port).to_socket_addrs()? {
+                    match TcpStream::connect_timeout(&addr, timeout) {
+                        Ok(s) => {
+                            stream = Some(s);
+                            break;
+                        }
+                        Err(e) => last_err = Some(e),
+                    }
+                }
+                match stream {
+                    Some(stream) => stream,
+                    None => {
+                        return Err(last_err
+                            .unwrap_or_else(|| {
+                                io::Error::new(
+                                    io::ErrorKind::NotFound,
+                                    format!("could not resolve {}", domain),
+                                )
+                            })
+                            .into());
+                    }
+                }
+            }
+        };
+        Ok(stream)
+    }
+}
+
+fn read_ca_file(path: &Path) -> PasteResult<Vec<Certificate>> {
+    let certs = X509::stack_from_pem(&fs::read(path)?)?;
+    if certs.is_empty() {
+        return Err(format!("no certificates found in {}", path.display()).into());
+    }
+    certs
+        .iter()
+        .map(|cert| Ok(Certificate::from_der(&cert.to_der()?)?))
+        .collect()
+}
+
+// Pkcs12Builder::build is deprecated in newer openssl releases in favour of build2, which the
+// version we depend on does not have yet
+#[allow(deprecated)]
+fn read_identity(cert_path: &Path, key_path: &Path) -> PasteResult<Identity> {
+    let mut certs = X509::stack_from_pem(&fs::read(cert_path)?)?.into_iter();
+    let cert = match certs.next() {
+        Some(cert) => cert,
+        None => return Err(format!("no certificate found in {}", cert_path.display()).into()),
+    };
+    let key = PKey::private_key_from_pem(&fs::read(key_path)?)?;
+    let mut chain = Stack::new()?;
+    for cert in certs {
+        chain.push(cert)?;
+    }
+
+    // the native tls backend can only load client identities from pkcs12
+    let mut pkcs12 = Pkcs12::builder();
+    pkcs12.ca(chain);
+    let der = pkcs12.build("pc", "pc", &key, &cert)?.to_der()?;
+    Ok(Identity::from_pkcs12_der(&der, "pc")?)
+}
+
+/// Whether `host` matches an entry in the `no_proxy` list.
+fn bypasses_proxy(no_proxy: &[String], host: &str) -> bool {
+    no_proxy.iter().any(|entry| {
+        let entry = entry.trim_start_matches('.');
+        entry == "*"
+            || host.eq_ignore_ascii_case(entry)
+            || (host.len() > entry.len()
+                && host
+                    .to_lowercase()
+                    .ends_with(&format!(".{}", entry.to_lowercase())))
+    })
+}
+
+fn connect_socks(proxy_url: &Url, domain: &str, port: u16) -> PasteResult<TcpStream> {
+    let proxy_addr = match proxy_url.host_str() {
+        Some(host) => (host, proxy_url.port().unwrap_or(1080)),
+        None => return Err(format!("no host in proxy url: {}", proxy_url).into()),
+    };
+
+    let stream = match proxy_url.scheme() {
+        // socks5h resolves the target domain on the proxy (needed for .onion addresses); socks5
+        // resolves it locally
+        "socks5h" => connect_socks_to(proxy_url, proxy_addr, (domain, port))?,
+        "socks5" => {
+            let target = match (domain, port).to_socket_addrs()?.next() {
+                Some(addr) => addr,
+                None => return Err(format!("could not resolve {}", domain).into()),
+            };
+            connect_socks_to(proxy_url, proxy_addr, target)?
+        }
+        scheme => {
+            return Err(format!(
+                "raw tcp connections can only go through a socks5 or socks5h proxy, not {}. \
+                 Add {} to no_proxy to connect directly.",
+                scheme, domain
+            )
+            .into());
+        }
+    };
+    Ok(stream.into_inner())
+}
+
+fn connect_socks_to<T: socks::ToTargetAddr>(
+    proxy_url: &Url,
+    proxy_addr: (&str, u16),
+    target: T,
+) -> io::Result<Socks5Stream> {
+    if proxy_url.username().is_empty() {
+        Socks5Stream::connect(proxy_addr, target)
+    } else {
+        Socks5Stream::connect_with_password(
+            proxy_addr,
+            target,
+            proxy_url.username(),
+            proxy_url.password().unwrap_or(""),
+        )
+    }
+}
+
+pub trait ResponseExt: Sized {
+    /// Turn responses that are worth retrying (429 Too Many Requests and 5xx server errors) into
+    /// errors, keeping any Retry-After delay the server asked for. Other responses are passed
+    /// through for the backend to interpret.
+    fn check_status(self) -> PasteResult<Self>;
+
+    /// As `check_status`, then turn any other error status into an error with the `message` a
+    /// json api gave in the body, eg. GitHub's and GitLab's.
+    fn check_api_status(self) -> PasteResult<Self>;
+
+    /// Read the whole body of a response, after turning error statuses into errors.
+    fn read_body(self) -> PasteResult<Vec<u8>>;
+}
+
+impl ResponseExt for Response {
+    fn check_status(self) -> PasteResult<Self> {
+        let status = self.status();
+        if status != StatusCode::TOO_MANY_REQUESTS && !status.is_server_error() {
+            return Ok(self);
+        }
+
+        // only the delay-seconds form of Retry-After is supported; http dates fall back to the
+        // normal backoff
+        let retry_after = self
+            .headers()
+            .get(RETRY_AFTER)
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| v.trim().parse().ok())
+            .map(Duration::from_secs);
+
+        Err(PasteError::Status {
+            status,
+            retry_after,
+        })
+    }
+
+    fn check_api_status(self) -> PasteResult<Self> {
+        let mut res = self.check_status()?;
+        let status = res.status();
+        if status.is_success() {
+            return Ok(res);
+        }
+        match res.json::<ApiError>() {
+            Ok(ApiError {
+                message: Value::String(message),
+            }) => Err(format!("api returned failure: {} ({})", message, status).into()),
+            // validation errors are reported by field
+            Ok(ApiError { message }) => {
+                Err(format!("api returned failure: {} ({})", message, status).into())
+            }
+            Err(_) => Err(format!("api returned failure: {}", status).into()),
+        }
+    }
+
+    fn read_body(self) -> PasteResult<Vec<u8>> {
+        let mut res = self.check_status()?.error_for_status()?;
+        let mut data = Vec::new();
+        res.copy_to(&mut data)?;
+        Ok(data)
+    }
+}
+
+/// Error body of a json api.
+#[derive(Debug, Deserialize)]
+struct ApiError {
+    message: Value,
+}
+
+/// A json api authenticated with a token, eg. GitHub's or GitLab's.
+pub struct TokenApi<'a> {
+    /// Url the endpoints are relative to. It can have a path, eg. /api/v3/ for GitHub Enterprise.
+    pub url: &'a Url,
+    /// Token from the config. When not set, it is read from `token_var` in the environment.
+    pub token: Option<&'a str>,
+    pub token_var: &'a str,
+    /// Header the token is sent in, and what goes before the token in it, eg. "token ".
+    pub header: &'static str,
+    pub scheme: &'static str,
+}
+
+impl TokenApi<'_> {
+    /// Start an authenticated request to an api endpoint.
+    pub fn request(
+        &self,
+        client_config: &ClientConfig,
+        method: Method,
+        endpoint: &str,
+    ) -> PasteResult<RequestBuilder> {
+        let token = match self.token {
+            Some(token) => token.to_owned(),
+            None => env::var(self.token_var).map_err(|_| {
+                format!(
+                    "a token is required; set token in the config, or {}",
+                    self.token_var
+                )
+            })?,
+        };
+        let auth = HeaderValue::from_str(&format!("{}{}", self.scheme, token))
+            .map_err(|e| e.to_string())?;
+
+        let url = with_trailing_slash(self.url).join(endpoint)?;
+        Ok(client_config
+            .build()?
+            .request(method, url)
+            .header(self.header, auth))
+    }
+}
+
+/// `url` with a trailing slash, so that paths can be joined onto it rather than replacing its
+/// last segment.
+pub fn with_trailing_slash(url: &Url) -> Url {
+    let mut url = url.clone();
+    if !url.path().ends_with('/') {
+        let path = format!("{}/", url.path());
+        url.set_path(&path);
+    }
+    url
+}
+
+/// Called before each retry with the error that caused it and the delay before the retry.
+pub type RetryHook = Arc<dyn Fn(&PasteError, Duration) + Send + Sync>;
+
+/// How often, and how quickly, to retry requests that failed with a transient error.
+#[derive(Clone)]
+pub struct RetryPolicy {
+    /// Number of retries after the first attempt.
+    pub retries: u32,
+    /// Delay before the first retry; doubled after each retry.
+    pub backoff: Duration,
+    /// Longest wait before a retry, for both the backoff and Retry-After delays asked for by the
+    /// server.
+    pub max_delay: Duration,
+    /// Told about each retry, eg. to let the user know why there is a wait. Default is None;
+    /// retries happen quietly.
+    pub on_retry: Option<RetryHook>,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        RetryPolicy {
+            retries: 2,
+            backoff: Duration::from_secs(1),
+            max_delay: Duration::from_secs(60),
+            on_retry: None,
+        }
+    }
+}
+
+impl fmt::Debug for RetryPolicy {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("RetryPolicy")
+            .field("retries", &self.retries)
+            .field("backoff", &self.backoff)
+            .field("max_delay", &self.max_delay)
+            .field("on_retry", &self.on_retry.as_ref().map(|_| "Fn"))
+            .finish()
+    }
+}
+
+impl RetryPolicy {
+    /// Run `f`, retrying with exponential backoff while it fails with transient errors. A
+    /// Retry-After delay requested by the server is used instead of the backoff when longer. No
+    /// delay is longer than `max_delay`.
+    pub fn run<T, F>(&self, mut f: F) -> PasteResult<T>
+    where
+        F: FnMut() -> PasteResult<T>,
+    {
+        let mut backoff = self.backoff.min(self.max_delay);
+        let mut attempt = 0;
+        loop {
+            match f() {
+                Err(ref e) if attempt < self.retries && is_transient(e) => {
+                    let delay = match e {
+                        PasteError::Status {
+                            retry_after: Some(retry_after),
+                            ..
+                        } => backoff.max(*retry_after).min(self.max_delay),
+                        _ => backoff,
+                    };
+                    if let Some(ref on_retry) = self.on_retry {
+                        on_retry(e, delay);
+                    }
+                    thread::sleep(delay);
+                    backoff = backoff
+                        .checked_mul(2)
+                        .map_or(self.max_delay, |b| b.min(self.max_delay));
+                    attempt += 1;
+                }
+                result => return result,
+            }
+        }
+    }
+}
+
+/// Whether an error is likely to go away by itself: dropped connections, timeouts, rate limiting
+/// and server errors.
+fn is_transient(err: &PasteError) -> bool {
+    match err {
+        PasteError::Status { .. } => true,
+        PasteError::Reqwest(e) => {
+            e.is_timeout()
+                || e.is_http()
+                || e.is_server_error()
+                || e.status() == Some(StatusCode::TOO_MANY_REQUESTS)
+                || e.get_ref()
+                    .and_then(|e| e.downcast_ref::<io::Error>())
+                    .map(is_transient_io)
+                    == Some(true)
+        }
+        PasteError::IO(e) => is_transient_io(e),
+        _ => false,
+    }
+}
+
+fn is_transient_io(err: &io::Error) -> bool {
+    matches!(
+        err.kind(),
+        io::ErrorKind::ConnectionRefused
+            | io::ErrorKind::ConnectionReset
+            | io::ErrorKind::ConnectionAborted
+            | io::ErrorKind::BrokenPipe
+            | io::ErrorKind::TimedOut
+            | io::ErrorKind::WouldBlock
+            | io::ErrorKind::Interrupted
+            | io::ErrorKind::UnexpectedEof
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+
+    fn unavailable(retry_after: Option<Duration>) -> PasteError {
+        PasteError::Status {
+            status: StatusCode::SERVICE_UNAVAILABLE,
+            retry_after,
+        }
+    }
+
+    /// A policy that records the delay before each retry.
+    fn recording_policy(
+        retries: u32,
+        backoff: Duration,
+        max_delay: Duration,
+    ) -> (RetryPolicy, Arc<Mutex<Vec<Duration>>>) {
+        let delays = Arc::new(Mutex::new(vec![]));
+        let recorded = Arc::clone(&delays);
+        let policy = RetryPolicy {
+            retries,
+            backoff,
+            max_delay,
+            on_retry: Some(Arc::new(move |_, delay| {
+                recorded.lock().unwrap().push(delay)
+            })),
+        };
+        (policy, delays)
+    }
+
+    #[test]
+    fn retries_transient_errors_with_backoff() {
+        let ms = Duration::from_millis;
+        let (policy, delays) = recording_policy(3, ms(1), ms(100));
+        let mut attempts = 0;
+        let result = policy.run(|| {
+            attempts += 1;
+            if attempts < 4 {
+                Err(unavailable(None))
+            } else {
+                Ok(attempts)
+            }
+        });
+        assert_eq!(result.unwrap(), 4);
+        assert_eq!(*delays.lock().unwrap(), vec![ms(1), ms(2), ms(4)]);
+    }
+
+    #[test]
+    fn gives_up_after_retries() {
+        let (policy, delays) =
+            recording_policy(2, Duration::from_millis(1), Duration::from_millis(100));
+        let mut attempts = 0;
+        let result: PasteResult<()> = policy.run(|| {
+            attempts += 1;
+            Err(unavailable(None))
+        });
+        assert_eq!(result.unwrap_err().code(), "server_status");
+        assert_eq!(attempts, 3);
+        assert_eq!(delays.lock().unwrap().len(), 2);
+    }
+
+    #[test]
+    fn other_errors_are_not_retried() {
+        let (policy, delays) =
+            recording_policy(2, Duration::from_millis(1), Duration::from_millis(100));
+        let mut attempts = 0;
+        let result: PasteResult<()> = policy.run(|| {
+            attempts += 1;
+            Err("invalid api key".to_owned().into())
+        });
+        assert!(result.is_err());
+        assert_eq!(attempts, 1);
+        assert!(delays.lock().unwrap().is_empty());
+    }
+
+    #[test]
+    fn delays_are_capped() {
+        let ms = Duration::from_millis;
+        // a backoff that would overflow when doubled
+        let (policy, delays) = recording_policy(3, Duration::from_secs(u64::MAX), ms(2));
+        let _: PasteResult<()> = policy.run(|| Err(unavailable(None)));
+        assert_eq!(*delays.lock().unwrap(), vec![ms(2); 3]);
+
+        // the server asking for a wait of hours
+        let (policy, delays) = recording_policy(1, ms(1), ms(2));
+        let _: PasteResult<()> =
+            policy.run(|| Err(unavailable(Some(Duration::from_secs(6 * 60 * 60)))));
+        assert_eq!(*delays.lock().unwrap(), vec![ms(2)]);
+    }
+
+    #[test]
+    fn retry_after_wins_over_shorter_backoff() {
+        let ms = Duration::from_millis;
+        let (policy, delays) = recording_policy(1, ms(1), ms(100));
+        let _: PasteResult<()> = policy.run(|| Err(unavailable(Some(ms(5)))));
+        assert_eq!(*delays.lock().unwrap(), vec![ms(5)]);
+    }
+
+    #[test]
+    fn token_api_joins_endpoints_onto_the_url_path() {
+        let url = Url::parse("https://example.com/api/v3").unwrap();
+        let api = TokenApi {
+            url: &url,
+            token: Some("secret"),
+            token_var: "PC_TEST_UNSET_TOKEN",
+            header: "Authorization",
+            scheme: "token ",
+        };
+        let request = api
+            .request(&ClientConfig::default(), Method::GET, "gists/abc")
+            .unwrap()
+            .build()
+            .unwrap();
+        assert_eq!(
+            request.url().as_str(),
+            "https://example.com/api/v3/gists/abc"
+        );
+        assert_eq!(request.headers()["Authorization"], "token secret");
+    }
+
+    #[test]
+    fn token_api_needs_a_token() {
+        let url = Url::parse("https://example.com/").unwrap();
+        let api = TokenApi {
+            url: &url,
+            token: None,
+            token_var: "PC_TEST_UNSET_TOKEN",
+            header: "PRIVATE-TOKEN",
+            scheme: "",
+        };
+        let err = api
+            .request(&ClientConfig::default(), Method::GET, "snippets")
+            .unwrap_err();
+        assert!(err.to_string().contains("PC_TEST_UNSET_TOKEN"));
+    }
+}
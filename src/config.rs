@@ -1,26 +1,113 @@
 use std::collections::HashMap;
 use std::default::Default;
 use std::env;
-use std::error::Error;
-use std::path::Path;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
 
 use serde::{Deserialize, Serialize};
+use url::Url;
 
 use crate::backends::BackendConfig;
-use crate::utils::read_file;
+use crate::client::{ClientConfig, RetryPolicy};
+use crate::error::{PasteError, PasteResult};
+use crate::utils::{read_file, serde_humantime};
 
-#[derive(Debug, Deserialize, Serialize)]
+#[derive(Debug, Deserialize, Serialize, Clone)]
 #[serde(deny_unknown_fields)]
 pub struct Config {
     pub main: MainConfig,
-    pub servers: HashMap<String, BackendConfig>,
+    pub servers: HashMap<String, ServerConfig>,
+    /// Extra names for the canonical syntax languages, eg. `py2 = "python"`.
+    #[serde(default)]
+    #[serde(skip_serializing_if = "HashMap::is_empty")]
+    pub syntax_aliases: HashMap<String, String>,
 }
 
-#[derive(Debug, Deserialize, Serialize)]
+/// A server block: backend specific settings, along with settings common to every backend.
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct ServerConfig {
+    #[serde(flatten)]
+    pub backend: BackendConfig,
+    /// Servers to try in order if pasting to this server fails. Overrides the main fallback list.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub fallback: Option<Vec<String>>,
+    /// Overrides the main encrypt setting for this server.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub encrypt: Option<bool>,
+    /// Overrides the main connect_timeout for this server.
+    #[serde(default)]
+    #[serde(with = "serde_humantime")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub connect_timeout: Option<Duration>,
+    /// Overrides the main timeout for this server.
+    #[serde(default)]
+    #[serde(with = "serde_humantime")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub timeout: Option<Duration>,
+    /// Overrides the main retries for this server.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub retries: Option<u32>,
+    /// Overrides the main retry_backoff for this server.
+    #[serde(default)]
+    #[serde(with = "serde_humantime")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub retry_backoff: Option<Duration>,
+    /// Overrides the main proxy for this server; "NONE" connects directly.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub proxy: Option<String>,
+    /// Overrides the main no_proxy list for this server.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub no_proxy: Option<Vec<String>>,
+    /// PEM file of extra CA certificates to trust for this server.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ca_file: Option<PathBuf>,
+    /// PEM client certificate to present to this server.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub client_cert: Option<PathBuf>,
+    /// PEM private key for client_cert, if not included in that file.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub client_key: Option<PathBuf>,
+    /// Skip verifying this server's certificate.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub insecure_skip_verify: Option<bool>,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone)]
 #[serde(deny_unknown_fields)]
 pub struct MainConfig {
     pub server: Option<String>,
     pub histfile: Option<String>,
+    pub tokenfile: Option<String>,
+    /// Template for printing the result of a paste, eg. "[{title}]({url})".
+    pub format: Option<String>,
+    /// Guess the syntax of pastes from file names and content. Default true.
+    pub detect_syntax: Option<bool>,
+    /// Encrypt pastes before they are sent, putting the key in the url fragment. Default false.
+    pub encrypt: Option<bool>,
+    /// Servers to try in order if pasting to the chosen server fails.
+    pub fallback: Option<Vec<String>>,
+    /// How long to skip fallback chain servers for after they fail.
+    #[serde(default)]
+    #[serde(with = "serde_humantime")]
+    pub cooldown: Option<Duration>,
+    /// Timeout for connecting to a server.
+    #[serde(default)]
+    #[serde(with = "serde_humantime")]
+    pub connect_timeout: Option<Duration>,
+    /// Timeout for reading from or writing to a server.
+    #[serde(default)]
+    #[serde(with = "serde_humantime")]
+    pub timeout: Option<Duration>,
+    /// Number of times to retry a request that failed with a transient error.
+    pub retries: Option<u32>,
+    /// Delay before the first retry; doubled for each retry after that.
+    #[serde(default)]
+    #[serde(with = "serde_humantime")]
+    pub retry_backoff: Option<Duration>,
+    /// Proxy url to connect to servers through.
+    pub proxy: Option<String>,
+    /// Hosts to connect to directly, bypassing the proxy.
+    pub no_proxy: Option<Vec<String>>,
 }
 
 impl Config {
@@ -49,6 +136,154 @@ impl Config {
             ..self
         }
     }
+
+    pub fn with_format_override(self, new_format: Option<String>) -> Self {
+        Config {
+            main: MainConfig {
+                format: match new_format {
+                    Some(ref c) if c.as_str() == "NONE" => None,
+                    _ => new_format.or(self.main.format),
+                },
+                ..self.main
+            },
+            ..self
+        }
+    }
+
+    /// Encrypt pastes to every server, whatever their server blocks say.
+    pub fn with_encrypt_override(mut self, encrypt: bool) -> Self {
+        if encrypt {
+            self.main.encrypt = Some(true);
+            for server_config in self.servers.values_mut() {
+                server_config.encrypt = None;
+            }
+        }
+        self
+    }
+
+    /// Name of the server to use when none is given: the configured default, otherwise an
+    /// arbitrary server block.
+    pub fn default_server(&self) -> PasteResult<String> {
+        if let Some(ref server) = self.main.server {
+            return Ok(server.to_owned());
+        }
+        match self.servers.keys().next() {
+            Some(server) => Ok(server.to_owned()),
+            None => Err(r#"No servers defined in configuration!
+Define one in the config file like:
+
+    [servers.rs]
+    backend = "paste_rs"
+    url = "https://paste.rs/""#
+                .to_owned()
+                .into()),
+        }
+    }
+
+    /// Find the server block called `server`.
+    pub fn server_config(&self, server: &str) -> PasteResult<ServerConfig> {
+        match self.servers.get(server) {
+            Some(server_config) => Ok(server_config.to_owned()),
+            None => Err(PasteError::UnknownServer(server.to_owned())),
+        }
+    }
+
+    /// The fallback servers to try after `server`, from its server block or the main config.
+    pub fn fallback_chain(
+        &self,
+        server: &str,
+        server_config: &ServerConfig,
+    ) -> PasteResult<Vec<(String, ServerConfig)>> {
+        let names = match server_config.fallback {
+            Some(ref names) => names,
+            None => match self.main.fallback {
+                Some(ref names) => names,
+                None => return Ok(vec![]),
+            },
+        };
+
+        let mut chain: Vec<(String, ServerConfig)> = Vec::with_capacity(names.len());
+        for name in names {
+            if name == server || chain.iter().any(|(n, _)| n == name) {
+                continue;
+            }
+            chain.push((name.to_owned(), self.server_config(name)?));
+        }
+        Ok(chain)
+    }
+
+    /// Network settings for a server block, falling back to the main config.
+    pub fn client_config(&self, server_config: &ServerConfig) -> ClientConfig {
+        ClientConfig {
+            connect_timeout: server_config.connect_timeout.or(self.main.connect_timeout),
+            timeout: server_config.timeout.or(self.main.timeout),
+            proxy: match server_config.proxy {
+                Some(ref proxy) if proxy == "NONE" => None,
+                Some(ref proxy) => Some(proxy.to_owned()),
+                None => self.main.proxy.clone(),
+            },
+            no_proxy: server_config
+                .no_proxy
+                .as_ref()
+                .or(self.main.no_proxy.as_ref())
+                .cloned()
+                .unwrap_or_default(),
+            ca_file: server_config.ca_file.clone(),
+            client_cert: server_config.client_cert.clone(),
+            client_key: server_config.client_key.clone(),
+            insecure_skip_verify: server_config.insecure_skip_verify.unwrap_or(false),
+        }
+    }
+
+    /// Whether pastes to a server block are encrypted, falling back to the main config.
+    pub fn encrypt(&self, server_config: &ServerConfig) -> bool {
+        server_config.encrypt.or(self.main.encrypt).unwrap_or(false)
+    }
+
+    /// Retry policy for a server block, falling back to the main config and then the defaults.
+    pub fn retry_policy(&self, server_config: &ServerConfig) -> RetryPolicy {
+        let default = RetryPolicy::default();
+        RetryPolicy {
+            retries: server_config
+                .retries
+                .or(self.main.retries)
+                .unwrap_or(default.retries),
+            backoff: server_config
+                .retry_backoff
+                .or(self.main.retry_backoff)
+                .unwrap_or(default.backoff),
+            ..default
+        }
+    }
+
+    /// Find the server block responsible for an existing paste url, by matching the url host
+    /// against the host of each configured server. The default server is preferred when several
+    /// server blocks share a host.
+    pub fn server_for_url(&self, url: &Url) -> Option<(String, ServerConfig)> {
+        let host = url.host_str()?;
+        let matches = |server_config: &ServerConfig| {
+            server_config
+                .backend
+                .clone()
+                .extract_backend()
+                .host()
+                .as_deref()
+                == Some(host)
+        };
+
+        if let Some(ref key) = self.main.server {
+            if let Some(server_config) = self.servers.get(key) {
+                if matches(server_config) {
+                    return Some((key.to_owned(), server_config.to_owned()));
+                }
+            }
+        }
+
+        self.servers
+            .iter()
+            .find(|(_, server_config)| matches(server_config))
+            .map(|(key, server_config)| (key.to_owned(), server_config.to_owned()))
+    }
 }
 
 impl Default for Config {
@@ -58,9 +293,7 @@ impl Default for Config {
     }
 }
 
-pub fn choose_config_file(
-    file_override: &Option<String>,
-) -> Result<Option<String>, Box<dyn Error>> {
+pub fn choose_config_file(file_override: &Option<String>) -> PasteResult<Option<String>> {
     match file_override {
         Some(s) => {
             // file override, use if exists, else err
@@ -76,7 +309,10 @@ pub fn choose_config_file(
             // no file override; find a file in the default locations
             let config_dir = match env::var("XDG_CONFIG_HOME") {
                 Ok(val) => val,
-                Err(_) => format!("{}/.config", env::var("HOME")?),
+                Err(_) => match env::var("HOME") {
+                    Ok(home) => format!("{}/.config", home),
+                    Err(e) => return Err(format!("could not find config directory: {}", e).into()),
+                },
             };
 
             let config_file = format!("{}/pc/config.toml", config_dir);
@@ -90,7 +326,17 @@ pub fn choose_config_file(
     }
 }
 
-pub fn read_config(path: &str) -> Result<Config, Box<dyn Error>> {
+/// File recording when servers last failed, used to skip them during the fallback cooldown.
+pub fn failures_file() -> Option<PathBuf> {
+    let cache_dir = match env::var("XDG_CACHE_HOME") {
+        Ok(val) => val,
+        Err(_) => format!("{}/.cache", env::var("HOME").ok()?),
+    };
+
+    Some(PathBuf::from(format!("{}/pc/failed_servers", cache_dir)))
+}
+
+pub fn read_config(path: &str) -> PasteResult<Config> {
     let data = read_file(path)?;
     let config = toml::from_str(&data)?;
     Ok(config)
@@ -0,0 +1,199 @@
+//! Client side encryption of pastes, for any backend.
+//!
+//! Content is encrypted with AES-256-GCM under a random key before it is pasted. The server only
+//! ever sees the ciphertext, armored as text so that backends accepting only text can take it.
+//! The key is added to the fragment of the paste url, which browsers and pc never send to the
+//! server, and `pc decrypt` reads it back from there.
+
+use openssl::rand::rand_bytes;
+use openssl::symm::{decrypt_aead, encrypt_aead, Cipher};
+use url::Url;
+
+use crate::error::PasteResult;
+
+/// First line of an encrypted paste, naming the format for anyone who finds it.
+const HEADER: &str = "-----BEGIN PC ENCRYPTED PASTE v1 (aes-256-gcm)-----";
+const FOOTER: &str = "-----END PC ENCRYPTED PASTE-----";
+/// Width the base64 ciphertext is wrapped at.
+const LINE_LEN: usize = 76;
+
+const KEY_LEN: usize = 32;
+const NONCE_LEN: usize = 12;
+const TAG_LEN: usize = 16;
+
+/// Encrypt `plaintext` with a new random key. Returns the armored ciphertext to paste, and the
+/// base58 key to add to the paste url.
+pub fn encrypt(plaintext: &[u8]) -> PasteResult<(String, String)> {
+    let mut key = [0; KEY_LEN];
+    let mut nonce = [0; NONCE_LEN];
+    rand_bytes(&mut key)?;
+    rand_bytes(&mut nonce)?;
+
+    let mut tag = [0; TAG_LEN];
+    let ct = encrypt_aead(
+        Cipher::aes_256_gcm(),
+        &key,
+        Some(&nonce),
+        HEADER.as_bytes(),
+        plaintext,
+        &mut tag,
+    )?;
+
+    let mut sealed = Vec::with_capacity(NONCE_LEN + ct.len() + TAG_LEN);
+    sealed.extend_from_slice(&nonce);
+    sealed.extend_from_slice(&ct);
+    sealed.extend_from_slice(&tag);
+    let encoded = base64::encode(&sealed);
+
+    let mut armored = String::with_capacity(encoded.len() * 2 + HEADER.len() + FOOTER.len());
+    armored.push_str(HEADER);
+    armored.push('\n');
+    for line in encoded.as_bytes().chunks(LINE_LEN) {
+        armored.push_str(std::str::from_utf8(line).expect("base64 is ascii"));
+        armored.push('\n');
+    }
+    armored.push_str(FOOTER);
+    armored.push('\n');
+    Ok((armored, bs58::encode(key).into_string()))
+}
+
+/// Decrypt a paste made with `encrypt`, using the base58 key from its url.
+pub fn decrypt(armored: &[u8], key: &str) -> PasteResult<Vec<u8>> {
+    let key = bs58::decode(key)
+        .into_vec()
+        .map_err(|e| format!("invalid key: {}", e))?;
+    if key.len() != KEY_LEN {
+        return Err(format!("invalid key: expected {} bytes, got {}", KEY_LEN, key.len()).into());
+    }
+
+    // servers may change line endings or add whitespace around the content
+    let armored = String::from_utf8_lossy(armored);
+    let mut lines = armored.lines().map(str::trim).skip_while(|l| l.is_empty());
+    if lines.next() != Some(HEADER) {
+        return Err("not a paste encrypted by pc".to_owned().into());
+    }
+    let encoded: String = lines.take_while(|line| *line != FOOTER).collect();
+    let sealed = base64::decode(&encoded).map_err(|e| format!("invalid ciphertext: {}", e))?;
+    if sealed.len() < NONCE_LEN + TAG_LEN {
+        return Err("ciphertext is too short".to_owned().into());
+    }
+    let (nonce, rest) = sealed.split_at(NONCE_LEN);
+    let (ct, tag) = rest.split_at(rest.len() - TAG_LEN);
+
+    decrypt_aead(
+        Cipher::aes_256_gcm(),
+        &key,
+        Some(nonce),
+        HEADER.as_bytes(),
+        ct,
+        tag,
+    )
+    .map_err(|_| {
+        "could not decrypt paste: wrong key, or the paste was changed"
+            .to_owned()
+            .into()
+    })
+}
+
+/// Add the key to the fragment of a paste url. Fails if the url already has a fragment, which pc
+/// would not be able to tell apart from the key.
+pub fn add_key(url: &mut Url, key: &str) -> PasteResult<()> {
+    if url.fragment().is_some() {
+        return Err(format!("no room for the key in the fragment of paste url: {}", url).into());
+    }
+    url.set_fragment(Some(key));
+    Ok(())
+}
+
+/// Split the key off the fragment of an encrypted paste url, leaving the url the server knows.
+pub fn take_key(url: &mut Url) -> PasteResult<String> {
+    let key = match url.fragment() {
+        Some(key) if !key.is_empty() => key.to_owned(),
+        _ => return Err(format!("no key in url (the part after #): {}", url).into()),
+    };
+    url.set_fragment(None);
+    Ok(key)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trip() {
+        let plaintext = b"some secret\nwith \xff binary content";
+        let (armored, key) = encrypt(plaintext).unwrap();
+        assert!(armored.starts_with(HEADER));
+        assert!(armored.trim_end().ends_with(FOOTER));
+        assert!(armored.lines().skip(1).all(|line| line.len() <= LINE_LEN));
+        assert_eq!(decrypt(armored.as_bytes(), &key).unwrap(), plaintext);
+    }
+
+    #[test]
+    fn round_trip_empty() {
+        let (armored, key) = encrypt(b"").unwrap();
+        assert_eq!(decrypt(armored.as_bytes(), &key).unwrap(), b"");
+    }
+
+    #[test]
+    fn decrypt_survives_changed_whitespace() {
+        let (armored, key) = encrypt(&[7; 200]).unwrap();
+        let mangled = format!("\n\n{}\n", armored.replace('\n', "  \r\n"));
+        assert_eq!(decrypt(mangled.as_bytes(), &key).unwrap(), vec![7; 200]);
+    }
+
+    #[test]
+    fn wrong_key_fails() {
+        let (armored, _) = encrypt(b"some secret").unwrap();
+        let (_, other_key) = encrypt(b"another secret").unwrap();
+        assert!(decrypt(armored.as_bytes(), &other_key).is_err());
+        // keys of the wrong length, and keys that aren't base58, are rejected before trying
+        assert!(decrypt(armored.as_bytes(), "abc").is_err());
+        assert!(decrypt(armored.as_bytes(), "0OIl").is_err());
+    }
+
+    #[test]
+    fn tampered_ciphertext_fails() {
+        let (armored, key) = encrypt(b"some secret that is long enough to change").unwrap();
+        // change a character past the nonce, in the ciphertext itself
+        let mut lines: Vec<String> = armored.lines().map(str::to_owned).collect();
+        let changed = if lines[1].as_bytes()[20] == b'A' {
+            "B"
+        } else {
+            "A"
+        };
+        lines[1].replace_range(20..21, changed);
+        let tampered = lines.join("\n");
+        assert!(decrypt(tampered.as_bytes(), &key).is_err());
+
+        let truncated = format!("{}\nAAAA\n{}\n", HEADER, FOOTER);
+        assert!(decrypt(truncated.as_bytes(), &key).is_err());
+        assert!(decrypt(b"not encrypted at all", &key).is_err());
+    }
+
+    #[test]
+    fn key_round_trip_through_url() {
+        let (_, key) = encrypt(b"some secret").unwrap();
+        let mut url = Url::parse("https://paste.example.com/abc").unwrap();
+        add_key(&mut url, &key).unwrap();
+        assert_eq!(url.fragment(), Some(key.as_str()));
+
+        assert_eq!(take_key(&mut url).unwrap(), key);
+        assert_eq!(url.as_str(), "https://paste.example.com/abc");
+    }
+
+    #[test]
+    fn add_key_refuses_existing_fragment() {
+        let mut url = Url::parse("https://paste.example.com/abc#L10").unwrap();
+        assert!(add_key(&mut url, "key").is_err());
+        assert_eq!(url.fragment(), Some("L10"));
+    }
+
+    #[test]
+    fn take_key_needs_a_key() {
+        let mut url = Url::parse("https://paste.example.com/abc").unwrap();
+        assert!(take_key(&mut url).is_err());
+        let mut url = Url::parse("https://paste.example.com/abc#").unwrap();
+        assert!(take_key(&mut url).is_err());
+    }
+}
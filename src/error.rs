@@ -1,5 +1,6 @@
 use std::error::Error;
 use std::fmt::{self, Display, Formatter};
+use std::time::Duration;
 
 #[derive(Debug)]
 pub enum PasteError {
@@ -8,11 +9,70 @@ pub enum PasteError {
     IO(std::io::Error),
     ParseInt(std::num::ParseIntError),
     ParseDuration(humantime::DurationError),
+    Tls(openssl::error::ErrorStack),
+    /// Invalid config file.
+    Config(toml::de::Error),
+    /// No server block with this name in the config.
+    UnknownServer(String),
+    /// No server block with a url on the same host as this one.
+    NoServerForUrl(url::Url),
+    /// No backend with this name.
+    UnknownBackend(String),
+    /// Input file that doesn't exist.
+    InputFileNotFound(String),
+    /// A command that reads the histfile was used without one configured.
+    NoHistfile(&'static str),
+    /// Histfile with no pastes recorded in it.
+    EmptyHistory(String),
+    /// Invalid url template.
+    Template(String),
+    /// Every server tried for a paste failed.
+    AllServersFailed,
+    /// Some of the uploads of a mirrored paste failed.
+    UploadsFailed {
+        failed: usize,
+        total: usize,
+    },
+    /// Invalid server args. Also returned when the args asked for help or version info, which
+    /// `clap::Error::exit` prints.
+    Args(clap::Error),
+    /// Server responded with a status worth retrying (429 or 5xx).
+    Status {
+        status: reqwest::StatusCode,
+        retry_after: Option<Duration>,
+    },
     Message(String),
 }
 
 pub type PasteResult<T> = Result<T, PasteError>;
 
+impl PasteError {
+    /// Short, stable name for the kind of error, for machine readable output.
+    pub fn code(&self) -> &'static str {
+        match self {
+            PasteError::Reqwest(_) => "request",
+            PasteError::Url(_) => "url",
+            PasteError::IO(_) => "io",
+            PasteError::ParseInt(_) => "parse_int",
+            PasteError::ParseDuration(_) => "parse_duration",
+            PasteError::Tls(_) => "tls",
+            PasteError::Config(_) => "config",
+            PasteError::UnknownServer(_) => "unknown_server",
+            PasteError::NoServerForUrl(_) => "no_server_for_url",
+            PasteError::UnknownBackend(_) => "unknown_backend",
+            PasteError::InputFileNotFound(_) => "input_file_not_found",
+            PasteError::NoHistfile(_) => "no_histfile",
+            PasteError::EmptyHistory(_) => "empty_history",
+            PasteError::Template(_) => "template",
+            PasteError::AllServersFailed => "all_servers_failed",
+            PasteError::UploadsFailed { .. } => "uploads_failed",
+            PasteError::Args(_) => "args",
+            PasteError::Status { .. } => "server_status",
+            PasteError::Message(_) => "other",
+        }
+    }
+}
+
 impl From<String> for PasteError {
     fn from(err: String) -> Self {
         PasteError::Message(err)
@@ -49,6 +109,24 @@ impl From<humantime::DurationError> for PasteError {
     }
 }
 
+impl From<openssl::error::ErrorStack> for PasteError {
+    fn from(err: openssl::error::ErrorStack) -> Self {
+        PasteError::Tls(err)
+    }
+}
+
+impl From<toml::de::Error> for PasteError {
+    fn from(err: toml::de::Error) -> Self {
+        PasteError::Config(err)
+    }
+}
+
+impl From<clap::Error> for PasteError {
+    fn from(err: clap::Error) -> Self {
+        PasteError::Args(err)
+    }
+}
+
 impl Display for PasteError {
     fn fmt(&self, f: &mut Formatter) -> fmt::Result {
         write!(
@@ -60,6 +138,35 @@ impl Display for PasteError {
                 PasteError::IO(err) => format!("IO error: {}", err),
                 PasteError::ParseInt(err) => format!("ParseInt error: {}", err),
                 PasteError::ParseDuration(err) => format!("ParseDuration error: {}", err),
+                PasteError::Tls(err) => format!("TLS error: {}", err),
+                PasteError::Config(err) => format!("Config error: {}", err),
+                PasteError::UnknownServer(server) => format!(
+                    r#"No corresponding server config for {0}.
+To use this, add a server block under the heading [servers.{0}] in the config toml file."#,
+                    server
+                ),
+                PasteError::NoServerForUrl(url) => format!(
+                    r#"No configured server matches the host of {}.
+Add a server block for it to the config toml file."#,
+                    url
+                ),
+                PasteError::UnknownBackend(backend) => {
+                    format!("{} is not a valid backend", backend)
+                }
+                PasteError::InputFileNotFound(file) => format!("input file not found: {}", file),
+                PasteError::NoHistfile(command) => {
+                    format!("{} requires a histfile to be configured", command)
+                }
+                PasteError::EmptyHistory(path) => {
+                    format!("no pastes recorded in histfile: {}", path)
+                }
+                PasteError::Template(err) => format!("Template error: {}", err),
+                PasteError::AllServersFailed => "all servers failed".to_owned(),
+                PasteError::UploadsFailed { failed, total } => {
+                    format!("{} of {} uploads failed", failed, total)
+                }
+                PasteError::Args(err) => err.message.clone(),
+                PasteError::Status { status, .. } => format!("Server error: {}", status),
                 PasteError::Message(err) => format!("other error: {}", err),
             }
         )
@@ -74,6 +181,19 @@ impl Error for PasteError {
             PasteError::IO(err) => Some(err),
             PasteError::ParseInt(err) => Some(err),
             PasteError::ParseDuration(err) => Some(err),
+            PasteError::Tls(err) => Some(err),
+            PasteError::Config(err) => Some(err),
+            PasteError::UnknownServer(_) => None,
+            PasteError::NoServerForUrl(_) => None,
+            PasteError::UnknownBackend(_) => None,
+            PasteError::InputFileNotFound(_) => None,
+            PasteError::NoHistfile(_) => None,
+            PasteError::EmptyHistory(_) => None,
+            PasteError::Template(_) => None,
+            PasteError::AllServersFailed => None,
+            PasteError::UploadsFailed { .. } => None,
+            PasteError::Args(err) => Some(err),
+            PasteError::Status { .. } => None,
             PasteError::Message(_) => None,
         }
     }
@@ -0,0 +1,416 @@
+//! The histfile: a record of pastes made, one per line.
+//!
+//! Each line is a json object describing a paste. Older histfiles hold just the paste url on each
+//! line; those are still read, as entries with nothing but the url.
+
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::path::Path;
+use std::time::SystemTime;
+
+use openssl::sha::Sha256;
+use serde::{Deserialize, Serialize};
+use url::Url;
+
+use crate::error::PasteResult;
+use crate::utils::{open_private_append, serde_url};
+
+/// A paste recorded in the histfile.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct HistoryEntry {
+    #[serde(with = "serde_url")]
+    pub url: Url,
+    /// When the paste was made.
+    #[serde(default, skip_serializing_if = "Option::is_none", with = "serde_time")]
+    pub time: Option<SystemTime>,
+    /// Name of the server block pasted to.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub server: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub backend: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub syntax: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none", with = "serde_time")]
+    pub expires: Option<SystemTime>,
+    /// Size of the content in bytes.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub size: Option<u64>,
+    /// Hex sha256 of the content.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sha256: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub delete_token: Option<String>,
+}
+
+impl From<Url> for HistoryEntry {
+    fn from(url: Url) -> Self {
+        HistoryEntry {
+            url,
+            time: None,
+            server: None,
+            backend: None,
+            title: None,
+            syntax: None,
+            expires: None,
+            size: None,
+            sha256: None,
+            delete_token: None,
+        }
+    }
+}
+
+impl HistoryEntry {
+    /// Whether the paste has passed its expiry time. Pastes with no known expiry never expire.
+    pub fn is_expired(&self, now: SystemTime) -> bool {
+        match self.expires {
+            Some(expires) => expires <= now,
+            None => false,
+        }
+    }
+}
+
+/// Which entries `pc history` lists. Every condition given must match; entries from old
+/// url-only histfiles don't match any condition on what they are missing.
+#[derive(Debug, Clone, Default)]
+pub struct HistoryFilter {
+    pub server: Option<String>,
+    pub since: Option<SystemTime>,
+    pub until: Option<SystemTime>,
+    /// Case insensitive substring of the title.
+    pub title: Option<String>,
+    pub unexpired: bool,
+}
+
+impl HistoryFilter {
+    pub fn matches(&self, entry: &HistoryEntry, now: SystemTime) -> bool {
+        if let Some(ref server) = self.server {
+            if entry.server.as_ref() != Some(server) {
+                return false;
+            }
+        }
+        if let Some(since) = self.since {
+            match entry.time {
+                Some(time) if time >= since => {}
+                _ => return false,
+            }
+        }
+        if let Some(until) = self.until {
+            match entry.time {
+                Some(time) if time <= until => {}
+                _ => return false,
+            }
+        }
+        if let Some(ref title) = self.title {
+            match entry.title {
+                Some(ref t) if t.to_lowercase().contains(&title.to_lowercase()) => {}
+                _ => return false,
+            }
+        }
+        !(self.unexpired && entry.is_expired(now))
+    }
+}
+
+/// Appends an entry to the histfile.
+pub fn write_hist(entry: &HistoryEntry, path: &str) -> PasteResult<()> {
+    let mut line = serde_json::to_string(entry).expect("must be valid");
+    line.push('\n');
+    let mut file = open_private_append(path)?;
+    file.write_all(line.as_bytes())?;
+    Ok(())
+}
+
+/// Reads all entries in the histfile, oldest first. Lines that can't be read are skipped, rather
+/// than losing the whole history to one bad line; a message for each is returned alongside.
+pub fn read_hist(path: &str) -> PasteResult<(Vec<HistoryEntry>, Vec<String>)> {
+    let file = BufReader::new(File::open(path)?);
+    let mut entries = vec![];
+    let mut invalid = vec![];
+    for (i, line) in file.lines().enumerate() {
+        let line = line?;
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+        let entry = if line.starts_with('{') {
+            serde_json::from_str(line).map_err(|e| e.to_string())
+        } else {
+            Url::parse(line)
+                .map(HistoryEntry::from)
+                .map_err(|e| e.to_string())
+        };
+        match entry {
+            Ok(entry) => entries.push(entry),
+            Err(e) => invalid.push(format!(
+                "skipped invalid histfile entry on line {} of {}: {}",
+                i + 1,
+                path,
+                e
+            )),
+        }
+    }
+    Ok((entries, invalid))
+}
+
+/// Returns the most recent valid paste recorded in the histfile.
+pub fn read_last_hist(path: &str) -> PasteResult<Option<HistoryEntry>> {
+    Ok(read_hist(path)?.0.pop())
+}
+
+/// Hex sha256 of some content, as recorded in the histfile.
+pub fn content_hash(data: &[u8]) -> String {
+    to_hex(&openssl::sha::sha256(data))
+}
+
+/// Hex sha256 of a file's content, read in chunks.
+pub fn file_hash<P: AsRef<Path>>(path: P) -> io::Result<String> {
+    let mut file = File::open(path)?;
+    let mut hasher = Sha256::new();
+    let mut buf = [0; 8192];
+    loop {
+        match file.read(&mut buf)? {
+            0 => break,
+            n => hasher.update(&buf[..n]),
+        }
+    }
+    Ok(to_hex(&hasher.finish()))
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+mod serde_time {
+    use serde::Deserialize;
+    use serde::{Deserializer, Serializer};
+    use std::time::SystemTime;
+
+    pub fn serialize<S>(x: &Option<SystemTime>, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        match x {
+            Some(x) => {
+                serializer.serialize_str(humantime::format_rfc3339_seconds(*x).to_string().as_str())
+            }
+            None => serializer.serialize_none(),
+        }
+    }
+
+    pub fn deserialize<'de, D>(d: D) -> Result<Option<SystemTime>, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let s = String::deserialize(d)?;
+
+        match humantime::parse_rfc3339_weak(&s) {
+            Ok(t) => Ok(Some(t)),
+            Err(_) => Err(serde::de::Error::custom(format!(
+                "Could not parse {:?} as a rfc3339 time",
+                s
+            ))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use std::path::PathBuf;
+    use std::time::Duration;
+
+    /// A histfile with the given content, removed when dropped.
+    struct Histfile(PathBuf);
+
+    impl Histfile {
+        fn new(name: &str, content: &str) -> Self {
+            let path = std::env::temp_dir().join(format!(
+                "pc-test-{}-{}.histfile",
+                std::process::id(),
+                name
+            ));
+            fs::write(&path, content).unwrap();
+            Histfile(path)
+        }
+
+        fn path(&self) -> &str {
+            self.0.to_str().unwrap()
+        }
+    }
+
+    impl Drop for Histfile {
+        fn drop(&mut self) {
+            let _ = fs::remove_file(&self.0);
+        }
+    }
+
+    fn time(s: &str) -> SystemTime {
+        humantime::parse_rfc3339(s).unwrap()
+    }
+
+    fn entry(url: &str) -> HistoryEntry {
+        HistoryEntry::from(Url::parse(url).unwrap())
+    }
+
+    #[test]
+    fn reads_legacy_lines_mixed_with_json() {
+        let histfile = Histfile::new(
+            "mixed",
+            "https://old.example/a\n\
+             \n\
+             {\"url\":\"https://new.example/b\",\"time\":\"2024-03-01T12:00:00Z\",\"server\":\"new\",\"title\":\"notes.txt\",\"size\":12,\"delete_token\":\"t0k\"}\n  \
+             https://old.example/c  \r\n\
+             {\"url\":\"https://new.example/d\"}\n",
+        );
+        let (entries, invalid) = read_hist(histfile.path()).unwrap();
+        assert!(invalid.is_empty());
+        assert_eq!(entries.len(), 4);
+
+        assert_eq!(entries[0], entry("https://old.example/a"));
+        assert_eq!(entries[1].url.as_str(), "https://new.example/b");
+        assert_eq!(entries[1].time, Some(time("2024-03-01T12:00:00Z")));
+        assert_eq!(entries[1].server.as_deref(), Some("new"));
+        assert_eq!(entries[1].title.as_deref(), Some("notes.txt"));
+        assert_eq!(entries[1].size, Some(12));
+        assert_eq!(entries[1].delete_token.as_deref(), Some("t0k"));
+        assert_eq!(entries[2], entry("https://old.example/c"));
+        assert_eq!(entries[3], entry("https://new.example/d"));
+
+        assert_eq!(
+            read_last_hist(histfile.path()).unwrap(),
+            Some(entry("https://new.example/d"))
+        );
+    }
+
+    #[test]
+    fn skips_invalid_lines() {
+        let histfile = Histfile::new(
+            "invalid",
+            "https://a.example/\n{\"url\":\"https://b.example/\",\nnot a url\nhttps://c.example/\n",
+        );
+        let (entries, invalid) = read_hist(histfile.path()).unwrap();
+        assert_eq!(
+            entries,
+            vec![entry("https://a.example/"), entry("https://c.example/")]
+        );
+        assert_eq!(invalid.len(), 2);
+        assert!(invalid[0].starts_with("skipped invalid histfile entry on line 2 of "));
+        assert!(invalid[1].starts_with("skipped invalid histfile entry on line 3 of "));
+
+        let histfile = Histfile::new("invalid-last", "https://a.example/\nnot a url\n");
+        assert_eq!(
+            read_last_hist(histfile.path()).unwrap(),
+            Some(entry("https://a.example/"))
+        );
+    }
+
+    #[test]
+    fn written_entries_read_back() {
+        let histfile = Histfile::new("written", "https://old.example/a\n");
+        let mut new = entry("https://new.example/b");
+        new.time = Some(time("2024-03-01T12:00:00Z"));
+        new.expires = Some(time("2024-03-02T12:00:00Z"));
+        new.sha256 = Some(content_hash(b"hello"));
+        write_hist(&new, histfile.path()).unwrap();
+
+        let (entries, invalid) = read_hist(histfile.path()).unwrap();
+        assert_eq!(entries, vec![entry("https://old.example/a"), new]);
+        assert!(invalid.is_empty());
+        assert_eq!(
+            read_last_hist(histfile.path()).unwrap(),
+            Some(entries[1].clone())
+        );
+
+        let empty = Histfile::new("empty", "");
+        assert_eq!(read_last_hist(empty.path()).unwrap(), None);
+    }
+
+    #[test]
+    fn filters() {
+        let now = time("2024-03-10T00:00:00Z");
+        let legacy = entry("https://old.example/a");
+        let mut recent = entry("https://new.example/b");
+        recent.time = Some(now - Duration::from_secs(60 * 60));
+        recent.server = Some("haste".to_owned());
+        recent.title = Some("Build Log.txt".to_owned());
+        recent.expires = Some(now - Duration::from_secs(60));
+        let mut older = entry("https://new.example/c");
+        older.time = Some(now - Duration::from_secs(7 * 24 * 60 * 60));
+        older.server = Some("gist".to_owned());
+        older.expires = Some(now + Duration::from_secs(60));
+
+        let matching = |filter: HistoryFilter| -> Vec<&str> {
+            [&legacy, &recent, &older]
+                .iter()
+                .filter(|entry| filter.matches(entry, now))
+                .map(|entry| entry.url.as_str())
+                .collect()
+        };
+
+        assert_eq!(matching(HistoryFilter::default()).len(), 3);
+        assert_eq!(
+            matching(HistoryFilter {
+                server: Some("gist".to_owned()),
+                ..HistoryFilter::default()
+            }),
+            vec!["https://new.example/c"]
+        );
+        assert_eq!(
+            matching(HistoryFilter {
+                since: Some(now - Duration::from_secs(24 * 60 * 60)),
+                ..HistoryFilter::default()
+            }),
+            vec!["https://new.example/b"]
+        );
+        assert_eq!(
+            matching(HistoryFilter {
+                until: Some(now - Duration::from_secs(24 * 60 * 60)),
+                ..HistoryFilter::default()
+            }),
+            vec!["https://new.example/c"]
+        );
+        assert_eq!(
+            matching(HistoryFilter {
+                title: Some("build log".to_owned()),
+                ..HistoryFilter::default()
+            }),
+            vec!["https://new.example/b"]
+        );
+        // entries with no known expiry are taken as unexpired
+        assert_eq!(
+            matching(HistoryFilter {
+                unexpired: true,
+                ..HistoryFilter::default()
+            }),
+            vec!["https://old.example/a", "https://new.example/c"]
+        );
+        assert!(matching(HistoryFilter {
+            server: Some("haste".to_owned()),
+            unexpired: true,
+            ..HistoryFilter::default()
+        })
+        .is_empty());
+    }
+
+    #[test]
+    fn hashes() {
+        let hash = "2cf24dba5fb0a30e26e83b2ac5b9e29e1b161e5c1fa7425e73043362938b9824";
+        assert_eq!(content_hash(b"hello"), hash);
+        let file = Histfile::new("hashed", "hello");
+        assert_eq!(file_hash(&file.0).unwrap(), hash);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn new_histfile_is_private() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let histfile = Histfile::new("private", "");
+        fs::remove_file(&histfile.0).unwrap();
+        write_hist(&entry("https://a.example/"), histfile.path()).unwrap();
+        let mode = fs::metadata(&histfile.0).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+    }
+}
@@ -0,0 +1,34 @@
+//! Command line client for pastebins, usable as a library.
+//!
+//! Servers are configured the same way as for the `pc` binary (see `example_config.toml`); the
+//! [`Paster`] builder takes it from there:
+//!
+//! ```no_run
+//! use pc::{PasteData, Paster};
+//!
+//! let info = Paster::from_config_file(None)?
+//!     .server("rs")
+//!     .paste(PasteData::from(b"hello".to_vec()))?;
+//! println!("{}", info.url);
+//! # Ok::<(), pc::PasteError>(())
+//! ```
+//!
+//! [`Paster`]: paster/struct.Paster.html
+
+pub mod backends;
+pub mod client;
+pub mod config;
+pub mod crypto;
+pub mod error;
+pub mod history;
+pub mod paster;
+pub mod syntax;
+pub mod types;
+pub mod utils;
+
+pub use crate::backends::BackendConfig;
+pub use crate::config::{Config, ServerConfig};
+pub use crate::error::{PasteError, PasteResult};
+pub use crate::history::HistoryEntry;
+pub use crate::paster::{Paster, Server};
+pub use crate::types::{PasteClient, PasteData, PasteInfo};
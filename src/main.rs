@@ -1,22 +1,39 @@
+use std::collections::HashMap;
+use std::env;
 use std::error::Error;
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::thread;
+use std::time::SystemTime;
 
 use clap::{crate_authors, crate_version, App, AppSettings, Arg, SubCommand};
+use url::Url;
 
-mod backends;
-mod config;
-mod error;
-mod types;
-mod utils;
+mod output;
 
-use crate::backends::{BackendConfig, BACKENDS_INFO};
-use crate::config::{choose_config_file, read_config, Config};
-use crate::utils::{read_stdin, write_hist};
+use crate::output::{
+    check_template, print_backend, print_backends, print_error, print_history, print_matrix,
+    print_paste, print_server_error, print_servers, print_warning, OutputFormat, Pasted,
+};
+use pc::backends::Capabilities;
+use pc::config::{choose_config_file, failures_file, read_config};
+use pc::crypto;
+use pc::history::{content_hash, file_hash, read_hist, read_last_hist, write_hist, HistoryFilter};
+use pc::syntax;
+use pc::utils::{read_failures, read_token, write_failures, write_token};
+use pc::{Config, HistoryEntry, PasteData, PasteError, PasteResult, Paster, Server};
 
 #[derive(Debug, Clone)]
 struct Opt {
+    output: OutputFormat,
     config_file: Option<String>,
     op: Op,
     histfile: Option<String>,
+    format: Option<String>,
+    no_detect_syntax: bool,
+    encrypt: bool,
 }
 
 #[derive(Debug, Clone)]
@@ -24,83 +41,661 @@ enum Op {
     Paste {
         server: Option<String>,
         server_args: Vec<String>,
+        files: Vec<String>,
+    },
+    List {
+        /// Only servers whose backends support all of these capabilities.
+        supports: Vec<String>,
     },
-    List,
     ShowBackend(String),
-    ListBackends,
+    ListBackends {
+        matrix: bool,
+    },
     DumpConfig,
+    Get(String),
+    Decrypt {
+        url: String,
+        /// Read the encrypted paste from stdin rather than fetching it.
+        stdin: bool,
+    },
+    Delete {
+        url: String,
+        token: Option<String>,
+    },
+    History(HistoryFilter),
+    Mirror {
+        servers: Vec<String>,
+        files: Vec<String>,
+    },
 }
 
-fn do_paste(config: Config, mut server_args: Vec<String>) -> Result<(), Box<dyn Error>> {
-    // sanity checking
-    if config.servers.is_empty() {
-        return Err(r#"No servers defined in configuration!
-Define one in the config file like:
+fn do_paste(
+    config: Config,
+    output: OutputFormat,
+    server_args: Vec<String>,
+    files: Vec<String>,
+) -> Result<(), Box<dyn Error>> {
+    let server_choice = config.default_server()?;
+    let server_config = config.server_config(&server_choice)?;
+    let chain = config.fallback_chain(&server_choice, &server_config)?;
+
+    // check all files up front so a typo doesn't leave some of them uploaded
+    for file in &files {
+        if !Path::new(file).is_file() {
+            return Err(PasteError::InputFileNotFound(file.to_owned()).into());
+        }
+    }
+
+    let inputs: Vec<Option<&Path>> = if files.is_empty() {
+        vec![None]
+    } else {
+        files.iter().map(|f| Some(Path::new(f))).collect()
+    };
+
+    // check the server args (and show --help) before waiting on stdin to guess the syntax
+    let chosen = Paster::new(config.clone())
+        .server(server_choice.as_str())
+        .args(server_args.iter().cloned());
+    let server = check_server(&chosen, output)?;
+
+    // backends that can keep several files in one paste get them all at once
+    if files.len() > 1 && server_config.backend.capabilities().multi_file && !server.encrypt {
+        let pasted = paste_files(&build_server(&chosen, output)?, &files)?;
+        return record_paste(&config, output, pasted);
+    }
+
+    let detect_syntax = config.main.detect_syntax.unwrap_or(true);
+    for path in inputs {
+        // look at the start of the content to guess its syntax; stdin is peeked at without
+        // losing what was read
+        let (head, stdin) = match path {
+            Some(path) if detect_syntax => {
+                (PasteData::file(path)?.peek(syntax::DETECT_LEN)?.0, None)
+            }
+            Some(_) => (vec![], None),
+            None if detect_syntax => {
+                let (head, stdin) = PasteData::stdin().peek(syntax::DETECT_LEN)?;
+                (head, Some(stdin))
+            }
+            None => (vec![], Some(PasteData::stdin())),
+        };
+
+        // server args only make sense for the chosen server; fallbacks use their config as is
+        let mut pasters = Vec::with_capacity(chain.len() + 1);
+        pasters.push(
+            Paster::new(config.clone())
+                .server(server_choice.as_str())
+                .args(server_args.iter().cloned()),
+        );
+        for (name, _) in &chain {
+            pasters.push(Paster::new(config.clone()).server(name.as_str()));
+        }
+
+        let mut servers = Vec::with_capacity(pasters.len());
+        for paster in pasters {
+            let paster = match path {
+                Some(path) => paster.file_defaults(path),
+                None => paster,
+            };
+            servers.push(build_server(&paster.content_defaults(&head), output)?);
+        }
+
+        let pasted = paste_with_fallback(&config, output, &servers, path, stdin)?;
+        record_paste(&config, output, pasted)?;
+    }
+
+    Ok(())
+}
 
-    [servers.rs]
-    backend = "paste_rs"
-    url = "https://paste.rs/""#
-            .into());
+/// Paste several files together as one paste. Fallback servers aren't tried, as they may not be
+/// able to hold several files.
+fn paste_files(server: &Server, files: &[String]) -> Result<Pasted, Box<dyn Error>> {
+    let mut contents = Vec::with_capacity(files.len());
+    for file in files {
+        let path = Path::new(file);
+        let name = match path.file_name() {
+            Some(name) => name.to_string_lossy().into_owned(),
+            None => return Err(format!("input file has no file name: {}", file).into()),
+        };
+        contents.push((name, fs::read(path)?));
     }
 
-    // config file if set, otherwise arbitrary server
-    let server_choice: String = config
-        .main
-        .server
-        .clone()
-        .unwrap_or_else(|| config.servers.keys().next().unwrap().to_owned());
+    Ok(Pasted {
+        size: Some(contents.iter().map(|(_, data)| data.len() as u64).sum()),
+        info: server.paste_files(&contents)?,
+        server: server.name.to_owned(),
+        backend: server.backend_name,
+        title: paste_title(server, None),
+        syntax: None,
+        sha256: None,
+    })
+}
 
-    let backend_config: BackendConfig = match config.servers.get(&server_choice) {
-        Some(choice) => choice.to_owned(),
-        None => {
-            return Err(format!(
-                r#"No corresponding server config for {0}.
-To use this, add a server block under the heading [servers.{0}] in the config toml file."#,
-                server_choice
-            )
-            .into());
+/// Paste the file at `path` (or `stdin`, if no path) to each server in turn until one succeeds,
+/// retrying transient failures. Servers that failed within the configured cooldown period are
+/// skipped, unless all of them have.
+fn paste_with_fallback(
+    config: &Config,
+    output: OutputFormat,
+    servers: &[Server],
+    path: Option<&Path>,
+    mut stdin: Option<PasteData>,
+) -> Result<Pasted, Box<dyn Error>> {
+    // the content may need to be sent more than once; files can simply be read again, but stdin
+    // must be buffered unless there will only be one attempt
+    let single_attempt = servers.len() == 1 && servers[0].retry_policy.retries == 0;
+    let stdin_data = match stdin.take() {
+        Some(data) if !single_attempt => Some(data.into_bytes()?),
+        data => {
+            stdin = data;
+            None
+        }
+    };
+    let mut open = || -> PasteResult<PasteData> {
+        match (path, &stdin_data) {
+            (Some(path), _) => Ok(PasteData::file(path)?),
+            (None, Some(data)) => Ok(PasteData::from(data.clone())),
+            (None, None) => Ok(stdin.take().unwrap_or_else(PasteData::stdin)),
         }
     };
 
-    server_args.insert(0, server_choice.clone());
+    let (size, sha256) = match (path, &stdin_data) {
+        (Some(path), _) => (Some(fs::metadata(path)?.len()), Some(file_hash(path)?)),
+        (None, Some(data)) => (Some(data.len() as u64), Some(content_hash(data))),
+        (None, None) => (None, None),
+    };
+    let pasted = |server: &Server, info| Pasted {
+        server: server.name.to_owned(),
+        backend: server.backend_name,
+        info,
+        title: paste_title(server, path),
+        syntax: server.backend.syntax().map(|s| s.to_owned()),
+        size,
+        sha256: sha256.clone(),
+    };
+
+    if servers.len() == 1 {
+        return Ok(pasted(&servers[0], servers[0].paste_with(&mut open)?));
+    }
+
+    let failures_path = config.main.cooldown.and_then(|_| failures_file());
+    let mut failures = match failures_path {
+        Some(ref path) => read_failures(path).unwrap_or_else(|e| {
+            eprintln!(
+                "error reading failed servers file {}: {}",
+                path.display(),
+                e
+            );
+            HashMap::new()
+        }),
+        None => HashMap::new(),
+    };
+
+    let now = SystemTime::now();
+    let cooling_down = |name: &str| match (config.main.cooldown, failures.get(name)) {
+        (Some(cooldown), Some(failed_at)) => match now.duration_since(*failed_at) {
+            Ok(elapsed) => elapsed < cooldown,
+            Err(_) => false,
+        },
+        _ => false,
+    };
+    let mut candidates: Vec<&Server> = servers
+        .iter()
+        .filter(|server| !cooling_down(&server.name))
+        .collect();
+    if candidates.is_empty() {
+        candidates = servers.iter().collect();
+    }
 
-    let mut backend = backend_config.clone().extract_backend();
+    let mut result = Err(PasteError::AllServersFailed.into());
+    for server in candidates {
+        match server.paste_with(&mut open) {
+            Ok(paste_info) => {
+                if server.name != servers[0].name && output == OutputFormat::Text {
+                    eprintln!("pasted to fallback server: {}", server.name);
+                }
+                failures.remove(&server.name);
+                result = Ok(pasted(server, paste_info));
+                break;
+            }
+            Err(e) => {
+                print_server_error(output, &server.name, &e);
+                failures.insert(server.name.to_owned(), SystemTime::now());
+                result = Err(PasteError::AllServersFailed.into());
+            }
+        }
+    }
 
-    if let Err(e) = backend.apply_args(server_args) {
-        if let clap::ErrorKind::HelpDisplayed = e.kind {
+    if let Some(ref path) = failures_path {
+        if let Err(e) = write_failures(&failures, path) {
             eprintln!(
-                "[servers.{}]\n{}---\n",
-                server_choice,
-                toml::to_string(&backend_config).expect("must be valid")
+                "error writing failed servers file {}: {}",
+                path.display(),
+                e
             );
         }
-        e.exit();
     }
 
-    let data = read_stdin()?;
-    let paste_url = backend.paste(data)?;
+    result
+}
+
+/// Upload the same content to several servers concurrently.
+fn do_mirror(
+    config: Config,
+    output: OutputFormat,
+    servers: Vec<String>,
+    files: Vec<String>,
+) -> Result<(), Box<dyn Error>> {
+    for server in &servers {
+        config.server_config(server)?;
+    }
+
+    for file in &files {
+        if !Path::new(file).is_file() {
+            return Err(PasteError::InputFileNotFound(file.to_owned()).into());
+        }
+    }
+
+    // every server needs its own copy of the content, so it can't be streamed
+    let inputs: Vec<(Option<PathBuf>, Vec<u8>)> = if files.is_empty() {
+        vec![(None, PasteData::stdin().into_bytes()?)]
+    } else {
+        let mut inputs = Vec::with_capacity(files.len());
+        for file in &files {
+            match PasteData::file(file).and_then(|data| data.into_bytes()) {
+                Ok(data) => inputs.push((Some(PathBuf::from(file)), data)),
+                Err(e) => {
+                    return Err(format!("error reading input file {}: {}", file, e).into());
+                }
+            }
+        }
+        inputs
+    };
+
+    let mut succeeded = vec![];
+    let mut failed = vec![];
+
+    for (path, data) in inputs {
+        let data = Arc::new(data);
+        let handles: Vec<_> = servers
+            .iter()
+            .map(|server| {
+                let paster = Paster::new(config.clone()).server(server.as_str());
+                let paster = match path {
+                    Some(ref path) => paster.file_defaults(path),
+                    None => paster,
+                };
+                let paster = paster.content_defaults(&data[..data.len().min(syntax::DETECT_LEN)]);
+                let data = Arc::clone(&data);
+                let path = path.clone();
+                let handle = thread::spawn(move || {
+                    let mut server = paster.build()?;
+                    for warning in &server.warnings {
+                        print_warning(output, &server.name, warning);
+                    }
+                    report_retries(&mut server, output);
+                    let info = server.paste_with(|| Ok(PasteData::from(data.to_vec())))?;
+                    Ok(Pasted {
+                        title: paste_title(&server, path.as_deref()),
+                        syntax: server.backend.syntax().map(|s| s.to_owned()),
+                        server: server.name,
+                        backend: server.backend_name,
+                        info,
+                        size: Some(data.len() as u64),
+                        sha256: Some(content_hash(&data)),
+                    })
+                });
+                (server, handle)
+            })
+            .collect();
+
+        for (server, handle) in handles {
+            let result: PasteResult<Pasted> = match handle.join() {
+                Ok(result) => result,
+                Err(_) => Err("upload thread panicked".to_owned().into()),
+            };
+            match result {
+                Ok(pasted) => {
+                    record_paste(&config, output, pasted)?;
+                    succeeded.push(server.as_str());
+                }
+                Err(e) => {
+                    print_server_error(output, server, &e);
+                    failed.push(server.as_str());
+                }
+            }
+        }
+    }
+
+    if failed.is_empty() {
+        return Ok(());
+    }
+
+    if output == OutputFormat::Text {
+        if !succeeded.is_empty() {
+            eprintln!("succeeded: {}", succeeded.join(", "));
+        }
+        eprintln!("failed: {}", failed.join(", "));
+    }
+    Err(PasteError::UploadsFailed {
+        failed: failed.len(),
+        total: failed.len() + succeeded.len(),
+    }
+    .into())
+}
+
+/// Title to report for a paste: the one the backend was given, or else the file name.
+fn paste_title(server: &Server, path: Option<&Path>) -> Option<String> {
+    match server.backend.title() {
+        Some(title) => Some(title.to_owned()),
+        None => path
+            .and_then(|path| path.file_name())
+            .map(|name| name.to_string_lossy().into_owned()),
+    }
+}
+
+/// Set up the server for a paster and print its warnings. Exits on invalid server args as
+/// `check_server` does.
+fn build_server(paster: &Paster, output: OutputFormat) -> Result<Server, Box<dyn Error>> {
+    let mut server = check_server(paster, output)?;
+    for warning in &server.warnings {
+        print_warning(output, &server.name, warning);
+    }
+    report_retries(&mut server, output);
+    Ok(server)
+}
+
+/// Print a warning for each retry of a request to the server, so a wait doesn't go unexplained.
+fn report_retries(server: &mut Server, output: OutputFormat) {
+    let name = server.name.clone();
+    server.retry_policy.on_retry = Some(Arc::new(move |e, delay| {
+        let warning = format!("{}; retrying in {}", e, humantime::format_duration(delay));
+        print_warning(output, &name, &warning);
+    }));
+}
+
+/// Set up the server for a paster, without printing its warnings. Exits on invalid server args (or
+/// after displaying help), unless errors are wanted as json.
+fn check_server(paster: &Paster, output: OutputFormat) -> Result<Server, Box<dyn Error>> {
+    match paster.build() {
+        Ok(server) => Ok(server),
+        Err(PasteError::Args(e)) => {
+            if let clap::ErrorKind::HelpDisplayed = e.kind {
+                let server_choice = paster.server_name()?;
+                eprintln!(
+                    "[servers.{}]\n{}---\n",
+                    server_choice,
+                    toml::to_string(&paster.config().server_config(&server_choice)?)
+                        .expect("must be valid")
+                );
+            }
+            match e.kind {
+                clap::ErrorKind::HelpDisplayed | clap::ErrorKind::VersionDisplayed => e.exit(),
+                _ if output == OutputFormat::Json => Err(PasteError::Args(e).into()),
+                _ => e.exit(),
+            }
+        }
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Output the url of a successful paste, and save it (and any delete token) as configured.
+fn record_paste(
+    config: &Config,
+    output: OutputFormat,
+    pasted: Pasted,
+) -> Result<(), Box<dyn Error>> {
+    // if there's nowhere to keep the delete token, make sure the user gets to see it
+    print_paste(
+        output,
+        config.main.format.as_deref(),
+        &pasted,
+        config.main.tokenfile.is_none(),
+    );
 
-    // send the url to stdout!
-    println!("{}", paste_url);
+    let paste_info = &pasted.info;
+    if let (Some(ref token), Some(ref path)) = (&paste_info.delete_token, &config.main.tokenfile) {
+        if let Err(e) = write_token(&paste_info.url, token, path) {
+            if output == OutputFormat::Text {
+                eprintln!("error writing to tokenfile: {}", path);
+            }
+            return Err(e.into());
+        }
+    }
 
     if let Some(ref path) = config.main.histfile {
-        match write_hist(paste_url, path) {
+        let entry = HistoryEntry {
+            url: paste_info.url.clone(),
+            time: Some(SystemTime::now()),
+            server: Some(pasted.server.clone()),
+            backend: Some(pasted.backend.to_owned()),
+            title: pasted.title.clone(),
+            syntax: pasted.syntax.clone(),
+            expires: paste_info.expires,
+            size: pasted.size,
+            sha256: pasted.sha256.clone(),
+            delete_token: paste_info.delete_token.clone(),
+        };
+        match write_hist(&entry, path) {
             Ok(_) => {}
             Err(e) => {
-                eprintln!("error writing to histfile: {}", path);
-                return Err(e);
+                if output == OutputFormat::Text {
+                    eprintln!("error writing to histfile: {}", path);
+                }
+                return Err(e.into());
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn do_get(config: Config, output: OutputFormat, url: String) -> Result<(), Box<dyn Error>> {
+    let data = fetch_paste(config, output, &Url::parse(&url).map_err(PasteError::from)?)?;
+    io::stdout().write_all(&data)?;
+    Ok(())
+}
+
+/// Print the decrypted content of a paste encrypted by pc, with the key from the url fragment.
+fn do_decrypt(
+    config: Config,
+    output: OutputFormat,
+    url: String,
+    stdin: bool,
+) -> Result<(), Box<dyn Error>> {
+    let mut url = Url::parse(&url).map_err(PasteError::from)?;
+    let key = crypto::take_key(&mut url)?;
+    let data = if stdin {
+        PasteData::stdin().into_bytes()?
+    } else {
+        fetch_paste(config, output, &url)?
+    };
+
+    io::stdout().write_all(&crypto::decrypt(&data, &key)?)?;
+
+    Ok(())
+}
+
+/// Fetch the raw content of a paste from the server block matching its url.
+fn fetch_paste(config: Config, output: OutputFormat, url: &Url) -> Result<Vec<u8>, Box<dyn Error>> {
+    let (server_choice, _) = match config.server_for_url(url) {
+        Some(server) => server,
+        None => return Err(PasteError::NoServerForUrl(url.clone()).into()),
+    };
+
+    let mut server = Paster::new(config).server(server_choice.as_str()).build()?;
+    report_retries(&mut server, output);
+    match server.fetch(url) {
+        Ok(data) => Ok(data),
+        Err(e) => {
+            if output == OutputFormat::Text {
+                eprintln!("error fetching paste from server: {}", server_choice);
             }
+            Err(e.into())
+        }
+    }
+}
+
+fn do_delete(
+    config: Config,
+    output: OutputFormat,
+    url: String,
+    token: Option<String>,
+) -> Result<(), Box<dyn Error>> {
+    let url = if url == "last" {
+        let path = match config.main.histfile {
+            Some(ref path) => path,
+            None => return Err(PasteError::NoHistfile("\"last\"").into()),
+        };
+        match read_last_hist(path)? {
+            Some(entry) => entry.url,
+            None => return Err(PasteError::EmptyHistory(path.to_owned()).into()),
+        }
+    } else {
+        Url::parse(&url).map_err(PasteError::from)?
+    };
+
+    let token = match (token, &config.main.tokenfile) {
+        (Some(token), _) => Some(token),
+        (None, Some(path)) if Path::new(path).exists() => read_token(&url, path)?,
+        (None, _) => None,
+    };
+    // the histfile also records delete tokens, so fall back to the latest one it has for the url
+    let token = match (token, &config.main.histfile) {
+        (Some(token), _) => Some(token),
+        (None, Some(path)) if Path::new(path).exists() => read_histfile(path)?
+            .into_iter()
+            .rev()
+            .find(|entry| entry.url == url && entry.delete_token.is_some())
+            .and_then(|entry| entry.delete_token),
+        (None, _) => None,
+    };
+
+    let (server_choice, _) = match config.server_for_url(&url) {
+        Some(server) => server,
+        None => return Err(PasteError::NoServerForUrl(url.clone()).into()),
+    };
+
+    let mut server = Paster::new(config).server(server_choice.as_str()).build()?;
+    report_retries(&mut server, output);
+    if let Err(e) = server.delete(&url, token.as_deref()) {
+        if output == OutputFormat::Text {
+            eprintln!("error deleting paste from server: {}", server_choice);
+        }
+        return Err(e.into());
+    }
+
+    if output == OutputFormat::Text {
+        eprintln!("deleted {}", url);
+    }
+
+    Ok(())
+}
+
+/// Read the histfile, warning about lines that had to be skipped.
+fn read_histfile(path: &str) -> PasteResult<Vec<HistoryEntry>> {
+    let (entries, invalid) = read_hist(path)?;
+    for message in invalid {
+        eprintln!("warning: {}", message);
+    }
+    Ok(entries)
+}
+
+/// List the pastes in the histfile that match `filter`, oldest first.
+fn do_history(
+    config: Config,
+    output: OutputFormat,
+    filter: HistoryFilter,
+) -> Result<(), Box<dyn Error>> {
+    let path = match config.main.histfile {
+        Some(ref path) => path,
+        None => return Err(PasteError::NoHistfile("history").into()),
+    };
+    let entries = if Path::new(path).exists() {
+        read_histfile(path)?
+    } else {
+        vec![]
+    };
+
+    let now = SystemTime::now();
+    let entries: Vec<HistoryEntry> = entries
+        .into_iter()
+        .filter(|entry| filter.matches(entry, now))
+        .collect();
+    print_history(output, &entries);
+    Ok(())
+}
+
+/// Parse a time for the history filters: a rfc3339 time, a date (taken as midnight utc), or a
+/// duration meaning that long ago (eg. "2days").
+fn parse_time_arg(name: &str, value: &str) -> Result<SystemTime, Box<dyn Error>> {
+    if let Ok(time) = humantime::parse_rfc3339_weak(value) {
+        return Ok(time);
+    }
+    if let Ok(time) = humantime::parse_rfc3339_weak(&format!("{} 00:00:00", value)) {
+        return Ok(time);
+    }
+    match humantime::parse_duration(value) {
+        Ok(ago) => Ok(SystemTime::now() - ago),
+        Err(_) => Err(args_error(
+            clap::ErrorKind::InvalidValue,
+            format!(
+                "invalid value for --{}: {:?}. Expected a date, time, or duration ago",
+                name, value
+            ),
+        )),
+    }
+}
+
+/// The output format given on the command line, if any. Found without clap, so that errors in
+/// the rest of the args can be reported in it.
+fn output_arg(args: &[String]) -> Option<OutputFormat> {
+    let mut format = None;
+    let mut args = args.iter().skip(1);
+    while let Some(arg) = args.next() {
+        let value = match arg.as_str() {
+            "-o" | "--output" => args.next().map(|s| s.as_str()),
+            _ => arg
+                .strip_prefix("--output=")
+                .or_else(|| arg.strip_prefix("-o")),
+        };
+        if let Some(value) = value.and_then(|v| v.parse().ok()) {
+            format = Some(value);
         }
     }
+    format
+}
+
+/// An error in the command line, reported with the same code as invalid server args.
+fn args_error(kind: clap::ErrorKind, message: String) -> Box<dyn Error> {
+    PasteError::Args(clap::Error {
+        message,
+        kind,
+        info: None,
+    })
+    .into()
+}
 
+/// Check the url template before pasting, so a typo doesn't cost an upload.
+fn check_format(config: &Config) -> Result<(), Box<dyn Error>> {
+    if let Some(ref format) = config.main.format {
+        check_template(format).map_err(PasteError::Template)?;
+    }
     Ok(())
 }
 
-fn run() -> Result<(), Box<dyn Error>> {
+/// Parse the command line and do what it asks. `output` is set from the command line as soon as
+/// it is known, so errors can be reported in the right format.
+fn run(output: &mut OutputFormat) -> Result<(), Box<dyn Error>> {
     let app = App::new("pc")
         .version(crate_version!())
         .author(crate_authors!())
         .setting(AppSettings::AllowExternalSubcommands)
+        .usage("pc [FLAGS] [OPTIONS] [SUBCOMMAND | SERVER [SERVER OPTIONS]] [-- FILE...]")
+        .after_help(
+            "Input is read from stdin, unless files are given after a `--`. Each file is \
+             uploaded as a separate paste, titled after the file name.",
+        )
         .arg(
             Arg::with_name("config")
                 .short("c")
@@ -117,8 +712,68 @@ fn run() -> Result<(), Box<dyn Error>> {
                 .help("Set a custom file to log to. \"NONE\" disables")
                 .takes_value(true),
         )
-        .subcommand(SubCommand::with_name("list").about("Print info about available server blocks"))
-        .subcommand(SubCommand::with_name("list-backends").about("Print available backends"))
+        .arg(
+            Arg::with_name("format")
+                .short("f")
+                .long("format")
+                .value_name("TEMPLATE")
+                .help(
+                    "Template for the printed url, eg. \"[{title}]({url})\". \"NONE\" \
+                     disables",
+                )
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("no-detect-syntax")
+                .long("no-detect-syntax")
+                .help("Don't guess the syntax from file names or content"),
+        )
+        .arg(
+            Arg::with_name("encrypt")
+                .long("encrypt")
+                .help("Encrypt pastes before sending them, adding the key to the url fragment"),
+        )
+        .arg(
+            Arg::with_name("output")
+                .short("o")
+                .long("output")
+                .value_name("FORMAT")
+                .help("Output format for results and errors")
+                .possible_values(&["text", "json"])
+                .default_value("text")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("mirror")
+                .short("m")
+                .long("mirror")
+                .value_name("SERVERS")
+                .help("Paste to several servers at once, given as a comma separated list")
+                .takes_value(true)
+                .use_delimiter(true),
+        )
+        .subcommand(
+            SubCommand::with_name("list")
+                .arg(
+                    Arg::with_name("supports")
+                        .long("supports")
+                        .value_name("CAPABILITIES")
+                        .help("Only list servers supporting all of these, eg. password,delete")
+                        .possible_values(&Capabilities::NAMES)
+                        .takes_value(true)
+                        .use_delimiter(true),
+                )
+                .about("Print info about available server blocks"),
+        )
+        .subcommand(
+            SubCommand::with_name("list-backends")
+                .arg(
+                    Arg::with_name("matrix")
+                        .long("matrix")
+                        .help("Print a table of the features each backend supports"),
+                )
+                .about("Print available backends"),
+        )
         .subcommand(
             SubCommand::with_name("dump-config").about("Print current config serialized as toml"),
         )
@@ -126,22 +781,175 @@ fn run() -> Result<(), Box<dyn Error>> {
             SubCommand::with_name("show-backend")
                 .arg(Arg::with_name("backend"))
                 .about("Show information about a backend"),
+        )
+        .subcommand(
+            SubCommand::with_name("get")
+                .arg(Arg::with_name("url").required(true))
+                .about("Print the raw content of an existing paste"),
+        )
+        .subcommand(
+            SubCommand::with_name("decrypt")
+                .arg(
+                    Arg::with_name("url")
+                        .required(true)
+                        .help("Url of the paste, including the key after the #"),
+                )
+                .arg(
+                    Arg::with_name("stdin")
+                        .long("stdin")
+                        .help("Read the encrypted paste from stdin instead of fetching it"),
+                )
+                .about("Print the decrypted content of a paste encrypted with --encrypt"),
+        )
+        .subcommand(
+            SubCommand::with_name("delete")
+                .arg(
+                    Arg::with_name("url")
+                        .required(true)
+                        .help("Url of the paste, or \"last\" for the last paste in the histfile"),
+                )
+                .arg(
+                    Arg::with_name("token")
+                        .short("t")
+                        .long("token")
+                        .value_name("TOKEN")
+                        .help("Delete token for the paste. Default is the token in the tokenfile")
+                        .takes_value(true),
+                )
+                .about("Delete an existing paste"),
+        )
+        .subcommand(
+            SubCommand::with_name("history")
+                .arg(
+                    Arg::with_name("server")
+                        .long("server")
+                        .value_name("SERVER")
+                        .help("Only list pastes to this server block")
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::with_name("since")
+                        .long("since")
+                        .value_name("TIME")
+                        .help(
+                            "Only list pastes made since a date, time, or duration ago (eg. 2days)",
+                        )
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::with_name("until")
+                        .long("until")
+                        .value_name("TIME")
+                        .help("Only list pastes made until a date, time, or duration ago")
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::with_name("title")
+                        .long("title")
+                        .value_name("TEXT")
+                        .help("Only list pastes with titles containing the text, ignoring case")
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::with_name("active")
+                        .long("active")
+                        .help("Only list pastes that haven't expired yet"),
+                )
+                .about("List pastes recorded in the histfile"),
         );
 
-    let matches = app.get_matches();
+    // input files come after a `--`. Split them off here, because clap can't combine trailing
+    // positional args with the external subcommands used for server names.
+    let mut args: Vec<String> = env::args().collect();
+    let files: Vec<String> = match args.iter().position(|a| a == "--") {
+        Some(i) => {
+            let files = args.split_off(i + 1);
+            args.pop();
+            files
+        }
+        None => vec![],
+    };
+
+    let files_given = !files.is_empty();
+
+    // errors in the args are reported as json when asked for, so they are parsed without clap
+    // printing them and exiting
+    let json_errors = output_arg(&args) == Some(OutputFormat::Json);
+    let app = if json_errors {
+        app.global_setting(AppSettings::ColorNever)
+    } else {
+        app
+    };
+    let matches = match app.get_matches_from_safe(&args) {
+        Ok(matches) => matches,
+        Err(e) => match e.kind {
+            clap::ErrorKind::HelpDisplayed | clap::ErrorKind::VersionDisplayed => e.exit(),
+            _ if json_errors => {
+                *output = OutputFormat::Json;
+                return Err(PasteError::Args(e).into());
+            }
+            _ => e.exit(),
+        },
+    };
+
+    *output = matches.value_of("output").expect("has default").parse()?;
+
+    if *output == OutputFormat::Json && matches.is_present("format") {
+        return Err(args_error(
+            clap::ErrorKind::ArgumentConflict,
+            "--format can't be used with --output json".to_owned(),
+        ));
+    }
+
+    if matches.is_present("mirror") && matches.subcommand_name().is_some() {
+        return Err(args_error(
+            clap::ErrorKind::ArgumentConflict,
+            "--mirror can't be used with a server or subcommand".to_owned(),
+        ));
+    }
 
     let op: Op = match matches.subcommand() {
-        ("list", _m) => Op::List,
+        ("list", Some(m)) => Op::List {
+            supports: match m.values_of("supports") {
+                Some(values) => values.map(|s| s.to_owned()).collect(),
+                None => vec![],
+            },
+        },
         ("dump-config", _m) => Op::DumpConfig,
-        ("list-backends", _m) => Op::ListBackends,
+        ("list-backends", Some(m)) => Op::ListBackends {
+            matrix: m.is_present("matrix"),
+        },
         ("show-backend", Some(m)) => {
             Op::ShowBackend(m.value_of("backend").expect("required param").to_owned())
         }
+        ("get", Some(m)) => Op::Get(m.value_of("url").expect("required param").to_owned()),
+        ("decrypt", Some(m)) => Op::Decrypt {
+            url: m.value_of("url").expect("required param").to_owned(),
+            stdin: m.is_present("stdin"),
+        },
+        ("delete", Some(m)) => Op::Delete {
+            url: m.value_of("url").expect("required param").to_owned(),
+            token: m.value_of("token").map(|s| s.to_owned()),
+        },
+        ("history", Some(m)) => Op::History(HistoryFilter {
+            server: m.value_of("server").map(|s| s.to_owned()),
+            since: match m.value_of("since") {
+                Some(since) => Some(parse_time_arg("since", since)?),
+                None => None,
+            },
+            until: match m.value_of("until") {
+                Some(until) => Some(parse_time_arg("until", until)?),
+                None => None,
+            },
+            title: m.value_of("title").map(|s| s.to_owned()),
+            unexpired: m.is_present("active"),
+        }),
         (external, Some(ext_m)) => {
             if matches.is_present("op") {
-                return Err("Extra commands can't be used when in paste mode"
-                    .to_owned()
-                    .into());
+                return Err(args_error(
+                    clap::ErrorKind::ArgumentConflict,
+                    "Extra commands can't be used when in paste mode".to_owned(),
+                ));
             }
             let ext_args: Vec<String> = match ext_m.values_of("") {
                 Some(values) => values.map(|s| s.to_owned()).collect(),
@@ -151,84 +959,119 @@ fn run() -> Result<(), Box<dyn Error>> {
             Op::Paste {
                 server: Some(external.to_owned()),
                 server_args: ext_args,
+                files,
             }
         }
-        ("", None) => Op::Paste {
-            server: None,
-            server_args: vec![],
+        ("", None) => match matches.values_of("mirror") {
+            Some(values) => Op::Mirror {
+                servers: values.map(|s| s.to_owned()).collect(),
+                files,
+            },
+            None => Op::Paste {
+                server: None,
+                server_args: vec![],
+                files,
+            },
         },
         _ => unreachable!(),
     };
 
+    match op {
+        Op::Paste { .. } | Op::Mirror { .. } => {}
+        _ if files_given => {
+            return Err(args_error(
+                clap::ErrorKind::ArgumentConflict,
+                "Input files can only be given when pasting".to_owned(),
+            ));
+        }
+        _ => {}
+    }
+
     let opt = Opt {
+        output: *output,
         histfile: matches.value_of("histfile").map(|s| s.to_owned()),
+        format: matches.value_of("format").map(|s| s.to_owned()),
+        no_detect_syntax: matches.is_present("no-detect-syntax"),
+        encrypt: matches.is_present("encrypt"),
         config_file: matches.value_of("config").map(|s| s.to_owned()),
         op,
     };
 
     let fname: Option<String> = choose_config_file(&opt.config_file)?;
-    let config = match fname {
+    let mut config = match fname {
         Some(path) => match read_config(&path) {
             Ok(config) => config,
             Err(e) => {
-                eprintln!("error with config file: {}", path);
-                return Err(e);
+                if opt.output == OutputFormat::Text {
+                    eprintln!("error with config file: {}", path);
+                }
+                return Err(e.into());
             }
         },
         None => Config::default(),
     };
 
+    if opt.no_detect_syntax {
+        config.main.detect_syntax = Some(false);
+    }
+
     match opt.op {
         Op::Paste {
             server,
             server_args,
+            files,
         } => {
             let config = config
                 .with_server_override(server)
-                .with_histfile_override(opt.histfile);
-            do_paste(config, server_args)
+                .with_histfile_override(opt.histfile)
+                .with_format_override(opt.format)
+                .with_encrypt_override(opt.encrypt);
+            check_format(&config)?;
+            do_paste(config, opt.output, server_args, files)
+        }
+        Op::Mirror { servers, files } => {
+            let config = config
+                .with_histfile_override(opt.histfile)
+                .with_format_override(opt.format)
+                .with_encrypt_override(opt.encrypt);
+            check_format(&config)?;
+            do_mirror(config, opt.output, servers, files)
+        }
+        Op::Get(url) => do_get(config, opt.output, url),
+        Op::Decrypt { url, stdin } => do_decrypt(config, opt.output, url, stdin),
+        Op::Delete { url, token } => {
+            let config = config.with_histfile_override(opt.histfile);
+            do_delete(config, opt.output, url, token)
+        }
+        Op::History(filter) => {
+            let config = config.with_histfile_override(opt.histfile);
+            do_history(config, opt.output, filter)
         }
         Op::DumpConfig => {
             println!("{}", toml::to_string(&config)?);
             Ok(())
         }
-        Op::List => {
-            for (key, backend_config) in config.servers.into_iter() {
-                println!(
-                    "{0} => {1}{2}",
-                    key,
-                    backend_config.extract_backend(),
-                    if config.main.server == Some(key.to_owned()) {
-                        " [default]"
-                    } else {
-                        ""
-                    }
-                );
-            }
+        Op::List { supports } => {
+            print_servers(opt.output, &config, &supports);
             Ok(())
         }
-        Op::ListBackends => {
-            let mut names = BACKENDS_INFO.keys().collect::<Vec<&&str>>();
-            names.sort();
-            for name in names {
-                println!("{}", name);
-            }
+        Op::ListBackends { matrix: false } => {
+            print_backends(opt.output);
             Ok(())
         }
-        Op::ShowBackend(name) => match BACKENDS_INFO.get(name.as_str()) {
-            Some(s) => {
-                println!("{}", s);
-                Ok(())
-            }
-            None => Err(format!("{} is not a valid backend", name).into()),
-        },
+        Op::ListBackends { matrix: true } => {
+            print_matrix(opt.output);
+            Ok(())
+        }
+        Op::ShowBackend(name) => print_backend(opt.output, &name),
     }
 }
 
 fn main() {
-    std::process::exit(match run() {
+    let mut output = OutputFormat::Text;
+    std::process::exit(match run(&mut output) {
         Err(err) => {
-            eprintln!("{}", err);
+            print_error(output, &*err);
             1
         }
         Ok(_) => 0,
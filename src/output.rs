@@ -0,0 +1,484 @@
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::str::FromStr;
+
+use serde::Serialize;
+use serde_json::json;
+
+use pc::backends::{
+    backend_options, Capabilities, ExpiryKind, OptionInfo, SyntaxKind, BACKENDS_CAPABILITIES,
+    BACKENDS_INFO,
+};
+use pc::{Config, HistoryEntry, PasteError, PasteInfo};
+use url::Url;
+
+/// How results are printed: as text for people, or as json for scripts.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum OutputFormat {
+    Text,
+    Json,
+}
+
+impl FromStr for OutputFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "text" => Ok(OutputFormat::Text),
+            "json" => Ok(OutputFormat::Json),
+            _ => Err(format!("unknown output format: {}", s)),
+        }
+    }
+}
+
+/// A successful paste, along with what is needed to report it.
+pub struct Pasted {
+    /// Name of the server block pasted to.
+    pub server: String,
+    pub backend: &'static str,
+    pub info: PasteInfo,
+    /// Title and syntax the backend was set up with, if it supports them.
+    pub title: Option<String>,
+    pub syntax: Option<String>,
+    /// Size and hex sha256 of the content, if known. Content streamed from stdin isn't measured.
+    pub size: Option<u64>,
+    pub sha256: Option<String>,
+}
+
+/// Placeholders that can be used in a `--format` template.
+const PLACEHOLDERS: [&str; 10] = [
+    "url",
+    "raw_url",
+    "manage_url",
+    "delete_token",
+    "server",
+    "backend",
+    "title",
+    "syntax",
+    "expires",
+    "size",
+];
+
+impl Pasted {
+    /// Value of a template placeholder; empty if the server didn't report it.
+    fn placeholder(&self, name: &str) -> String {
+        let info = &self.info;
+        let url = |url: &Option<Url>| url.as_ref().map(|u| u.to_string());
+        let value = match name {
+            "url" => Some(info.url.to_string()),
+            "raw_url" => url(&info.raw_url),
+            "manage_url" => url(&info.manage_url),
+            "delete_token" => info.delete_token.clone(),
+            "server" => Some(self.server.clone()),
+            "backend" => Some(self.backend.to_owned()),
+            "title" => self.title.clone(),
+            "syntax" => self.syntax.clone(),
+            "expires" => info
+                .expires
+                .map(|t| humantime::format_rfc3339_seconds(t).to_string()),
+            "size" => self.size.map(|s| s.to_string()),
+            _ => None,
+        };
+        value.unwrap_or_default()
+    }
+}
+
+/// Fill in the `{placeholder}`s in a template with `value`. `{{` and `}}` are literal braces.
+fn fill_template<F>(template: &str, value: F) -> Result<String, String>
+where
+    F: Fn(&str) -> String,
+{
+    let mut out = String::with_capacity(template.len());
+    let mut chars = template.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '{' if chars.peek() == Some(&'{') => {
+                chars.next();
+                out.push('{');
+            }
+            '}' if chars.peek() == Some(&'}') => {
+                chars.next();
+                out.push('}');
+            }
+            '{' => {
+                let mut name = String::new();
+                loop {
+                    match chars.next() {
+                        Some('}') => break,
+                        Some(c) => name.push(c),
+                        None => return Err("unterminated { in format".to_owned()),
+                    }
+                }
+                if !PLACEHOLDERS.contains(&name.as_str()) {
+                    return Err(format!(
+                        "unknown placeholder in format: {{{}}}. Valid placeholders are: {{{}}}",
+                        name,
+                        PLACEHOLDERS.join("}, {")
+                    ));
+                }
+                out.push_str(&value(&name));
+            }
+            '}' => return Err("unmatched } in format; use }} for a literal }".to_owned()),
+            c => out.push(c),
+        }
+    }
+    Ok(out)
+}
+
+/// Check that a format template is valid, before anything is pasted.
+pub fn check_template(template: &str) -> Result<(), String> {
+    fill_template(template, |_| String::new()).map(|_| ())
+}
+
+#[derive(Serialize)]
+struct PastedJson<'a> {
+    server: &'a str,
+    backend: &'a str,
+    url: &'a str,
+    raw_url: Option<&'a str>,
+    manage_url: Option<&'a str>,
+    delete_token: Option<&'a str>,
+    expires: Option<String>,
+    password_required: bool,
+    extras: &'a BTreeMap<String, String>,
+    size: Option<u64>,
+}
+
+/// Print the result of a paste. The url (or `template` filled in, if given) goes to stdout, along
+/// with everything else in json mode; in text mode the rest goes to stderr so stdout is just the
+/// url. The delete token is only shown if `show_token`.
+pub fn print_paste(
+    format: OutputFormat,
+    template: Option<&str>,
+    pasted: &Pasted,
+    show_token: bool,
+) {
+    let info = &pasted.info;
+    let delete_token = info.delete_token.as_deref().filter(|_| show_token);
+
+    if format == OutputFormat::Json {
+        let json = PastedJson {
+            server: &pasted.server,
+            backend: pasted.backend,
+            url: info.url.as_str(),
+            raw_url: info.raw_url.as_ref().map(|u| u.as_str()),
+            manage_url: info.manage_url.as_ref().map(|u| u.as_str()),
+            delete_token,
+            expires: info
+                .expires
+                .map(|t| humantime::format_rfc3339_seconds(t).to_string()),
+            password_required: info.password_required,
+            extras: &info.extras,
+            size: pasted.size,
+        };
+        println!("{}", serde_json::to_string(&json).expect("must be valid"));
+        return;
+    }
+
+    // send the url to stdout!
+    match template {
+        Some(template) => println!(
+            "{}",
+            fill_template(template, |name| pasted.placeholder(name)).expect("checked up front")
+        ),
+        None => println!("{}", info.url),
+    }
+
+    if let Some(ref raw_url) = info.raw_url {
+        if raw_url != &info.url {
+            eprintln!("raw: {}", raw_url);
+        }
+    }
+    if let Some(ref manage_url) = info.manage_url {
+        eprintln!("manage: {}", manage_url);
+    }
+    if let Some(expires) = info.expires {
+        eprintln!("expires: {}", humantime::format_rfc3339_seconds(expires));
+    }
+    if info.password_required {
+        eprintln!("password required");
+    }
+    for (name, value) in &info.extras {
+        eprintln!("{}: {}", name, value);
+    }
+    if let Some(token) = delete_token {
+        eprintln!("delete token: {}", token);
+    }
+}
+
+fn error_json(err: &(dyn Error + 'static), server: Option<&str>) -> serde_json::Value {
+    let code = match err.downcast_ref::<PasteError>() {
+        Some(err) => err.code(),
+        None => "other",
+    };
+    let mut error = json!({
+        "code": code,
+        "message": err.to_string(),
+    });
+    if let Some(server) = server {
+        error["server"] = json!(server);
+    }
+    json!({ "error": error })
+}
+
+/// Print an error that stops pc.
+pub fn print_error(format: OutputFormat, err: &(dyn Error + 'static)) {
+    match format {
+        OutputFormat::Text => eprintln!("{}", err),
+        OutputFormat::Json => eprintln!("{}", error_json(err, None)),
+    }
+}
+
+/// Print an error from one of several servers being pasted to.
+pub fn print_server_error(format: OutputFormat, server: &str, err: &PasteError) {
+    match format {
+        OutputFormat::Text => eprintln!("error pasting to server {}: {}", server, err),
+        OutputFormat::Json => eprintln!("{}", error_json(err, Some(server))),
+    }
+}
+
+/// Print a problem with a server's settings that didn't stop it being used.
+pub fn print_warning(format: OutputFormat, server: &str, warning: &str) {
+    match format {
+        OutputFormat::Text => eprintln!("warning for server {}: {}", server, warning),
+        OutputFormat::Json => {
+            eprintln!(
+                "{}",
+                json!({ "warning": { "server": server, "message": warning } })
+            )
+        }
+    }
+}
+
+/// Print the configured server blocks, only those whose backends support all of `supports`. Only
+/// where each server is gets printed, never the rest of its settings, which may hold credentials.
+pub fn print_servers(format: OutputFormat, config: &Config, supports: &[String]) {
+    let supported = |name: &String| {
+        let capabilities = config.servers[name].backend.capabilities();
+        supports
+            .iter()
+            .all(|cap| capabilities.supports(cap).unwrap_or(false))
+    };
+
+    if format == OutputFormat::Text {
+        for (key, server_config) in config.servers.iter().filter(|(key, _)| supported(key)) {
+            println!(
+                "{0} => {1}{2}",
+                key,
+                server_config.backend.clone().extract_backend(),
+                if config.main.server.as_ref() == Some(key) {
+                    " [default]"
+                } else {
+                    ""
+                }
+            );
+        }
+        return;
+    }
+
+    let mut names: Vec<&String> = config.servers.keys().filter(|key| supported(key)).collect();
+    names.sort();
+    let servers: Vec<serde_json::Value> = names
+        .into_iter()
+        .map(|name| {
+            let server_config = &config.servers[name];
+            json!({
+                "name": name,
+                "backend": server_config.backend.name(),
+                "url": server_config.backend.url(),
+                "default": config.main.server.as_ref() == Some(name),
+            })
+        })
+        .collect();
+    println!("{}", json!(servers));
+}
+
+/// Print the names of the available backends.
+pub fn print_backends(format: OutputFormat) {
+    let mut names = BACKENDS_INFO.keys().collect::<Vec<&&str>>();
+    names.sort();
+
+    match format {
+        OutputFormat::Text => {
+            for name in names {
+                println!("{}", name);
+            }
+        }
+        OutputFormat::Json => {
+            let backends: Vec<serde_json::Value> = names
+                .into_iter()
+                .map(|name| {
+                    json!({
+                        "name": name,
+                        "info": BACKENDS_INFO[name],
+                        "capabilities": BACKENDS_CAPABILITIES[name],
+                    })
+                })
+                .collect();
+            println!("{}", json!(backends));
+        }
+    }
+}
+
+/// Print information about a backend, including the server args it accepts in json mode.
+pub fn print_backend(format: OutputFormat, name: &str) -> Result<(), Box<dyn Error>> {
+    let info = match BACKENDS_INFO.get(name) {
+        Some(info) => info,
+        None => return Err(PasteError::UnknownBackend(name.to_owned()).into()),
+    };
+
+    match format {
+        OutputFormat::Text => println!("{}", info),
+        OutputFormat::Json => {
+            let options: Vec<OptionInfo> = backend_options(name).unwrap_or_default();
+            println!(
+                "{}",
+                json!({
+                    "name": name,
+                    "info": info,
+                    "capabilities": BACKENDS_CAPABILITIES[name],
+                    "options": options,
+                })
+            );
+        }
+    }
+    Ok(())
+}
+
+/// Print pastes from the histfile, one per line in text mode.
+pub fn print_history(format: OutputFormat, entries: &[HistoryEntry]) {
+    if format == OutputFormat::Json {
+        println!("{}", serde_json::to_string(entries).expect("must be valid"));
+        return;
+    }
+
+    for entry in entries {
+        let mut line = match entry.time {
+            Some(time) => humantime::format_rfc3339_seconds(time).to_string(),
+            // entries from old histfiles only have the url
+            None => "-".to_owned(),
+        };
+        line.push_str(&format!(
+            " {} {}",
+            entry.server.as_ref().map_or("-", |s| s.as_str()),
+            entry.url
+        ));
+        if let Some(ref title) = entry.title {
+            line.push_str(&format!(" {:?}", title));
+        }
+        println!("{}", line);
+    }
+}
+
+/// Print what each backend supports, as a markdown table in text mode.
+pub fn print_matrix(format: OutputFormat) {
+    let mut names = BACKENDS_CAPABILITIES.keys().collect::<Vec<&&str>>();
+    names.sort();
+
+    if format == OutputFormat::Json {
+        let backends: Vec<serde_json::Value> = names
+            .into_iter()
+            .map(|name| json!({ "name": name, "capabilities": BACKENDS_CAPABILITIES[name] }))
+            .collect();
+        println!("{}", json!(backends));
+        return;
+    }
+
+    let mark = |supported: bool| if supported { "x" } else { "" }.to_owned();
+    let mut rows = vec![vec![
+        "backend".to_owned(),
+        "title".to_owned(),
+        "expiry".to_owned(),
+        "password".to_owned(),
+        "auth".to_owned(),
+        "author".to_owned(),
+        "syntax".to_owned(),
+        "delete".to_owned(),
+        "fetch".to_owned(),
+        "multi file".to_owned(),
+        "max size".to_owned(),
+    ]];
+    for name in names {
+        let caps: &Capabilities = &BACKENDS_CAPABILITIES[name];
+        rows.push(vec![
+            (*name).to_owned(),
+            mark(caps.title),
+            match caps.expiry {
+                ExpiryKind::None => String::new(),
+                ExpiryKind::Duration => "duration".to_owned(),
+                ExpiryKind::Choice => "choice".to_owned(),
+            },
+            mark(caps.password),
+            mark(caps.auth),
+            mark(caps.author),
+            match caps.syntax {
+                SyntaxKind::None => String::new(),
+                SyntaxKind::Given => "x".to_owned(),
+                SyntaxKind::Detected => "detected".to_owned(),
+            },
+            mark(caps.delete),
+            mark(caps.fetch),
+            mark(caps.multi_file),
+            caps.max_size.map(|s| s.to_string()).unwrap_or_default(),
+        ]);
+    }
+
+    let widths: Vec<usize> = (0..rows[0].len())
+        .map(|i| rows.iter().map(|row| row[i].len()).max().unwrap_or(0))
+        .collect();
+    let print_row = |row: &[String]| {
+        let cells: Vec<String> = row
+            .iter()
+            .zip(&widths)
+            .map(|(cell, width)| format!("{:width$}", cell, width = width))
+            .collect();
+        println!("| {} |", cells.join(" | "));
+    };
+    print_row(&rows[0]);
+    let rule: Vec<String> = widths.iter().map(|width| "-".repeat(*width)).collect();
+    print_row(&rule);
+    for row in &rows[1..] {
+        print_row(row);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fill(template: &str) -> Result<String, String> {
+        fill_template(template, |name| format!("<{}>", name))
+    }
+
+    #[test]
+    fn fills_placeholders() {
+        assert_eq!(fill("[{title}]({url})").unwrap(), "[<title>](<url>)");
+    }
+
+    #[test]
+    fn escaped_braces_are_literal() {
+        assert_eq!(fill("{{url}} }}{url}{{").unwrap(), "{url} }<url>{");
+    }
+
+    #[test]
+    fn rejects_unknown_placeholder() {
+        let err = fill("{nope}").unwrap_err();
+        assert!(err.starts_with("unknown placeholder in format: {nope}"));
+        assert!(check_template("{nope}").is_err());
+    }
+
+    #[test]
+    fn rejects_unmatched_closing_brace() {
+        assert!(fill("url}").unwrap_err().starts_with("unmatched }"));
+    }
+
+    #[test]
+    fn rejects_unterminated_brace() {
+        assert_eq!(fill("{url").unwrap_err(), "unterminated { in format");
+        assert_eq!(
+            check_template("x {").unwrap_err(),
+            "unterminated { in format"
+        );
+        assert!(check_template("{url}").is_ok());
+    }
+}
@@ -0,0 +1,361 @@
+use std::path::Path;
+
+use url::Url;
+
+use crate::backends::{privatebin, BACKENDS_SYNTAXES};
+use crate::client::{ClientConfig, RetryPolicy};
+use crate::config::{choose_config_file, read_config, Config};
+use crate::crypto;
+use crate::error::PasteResult;
+use crate::syntax;
+use crate::types::{PasteClient, PasteData, PasteInfo};
+
+/// Builder for pasting to a configured server: load the config, pick a server, set options, and
+/// paste.
+///
+/// ```no_run
+/// use pc::{Paster, PasteData};
+///
+/// let info = Paster::from_config_file(None)?
+///     .server("fedora")
+///     .args(vec!["--title", "foo debug log"])
+///     .paste(PasteData::from(b"hello".to_vec()))?;
+/// println!("{}", info.url);
+/// # Ok::<(), pc::PasteError>(())
+/// ```
+#[derive(Debug, Clone)]
+pub struct Paster {
+    config: Config,
+    server: Option<String>,
+    args: Vec<String>,
+    default_title: Option<String>,
+    default_file_name: Option<String>,
+    default_syntax: Option<String>,
+    detect_syntax: bool,
+}
+
+impl Paster {
+    pub fn new(config: Config) -> Self {
+        Paster {
+            detect_syntax: config.main.detect_syntax.unwrap_or(true),
+            config,
+            server: None,
+            args: vec![],
+            default_title: None,
+            default_file_name: None,
+            default_syntax: None,
+        }
+    }
+
+    /// Load the config file at `path`, or from the default location
+    /// ($XDG_CONFIG_HOME/pc/config.toml) if not given. The built in default config is used if
+    /// there is no config file.
+    pub fn from_config_file(path: Option<&str>) -> PasteResult<Self> {
+        let config = match choose_config_file(&path.map(|p| p.to_owned()))? {
+            Some(path) => read_config(&path)?,
+            None => Config::default(),
+        };
+        Ok(Paster::new(config))
+    }
+
+    pub fn config(&self) -> &Config {
+        &self.config
+    }
+
+    /// The server block to paste to. Default is the default server from the config.
+    pub fn server<S: Into<String>>(mut self, server: S) -> Self {
+        self.server = Some(server.into());
+        self
+    }
+
+    /// Add a server arg, as would be given after the server name on the command line. See
+    /// `pc <server> --help` for the args each backend accepts.
+    pub fn arg<S: Into<String>>(mut self, arg: S) -> Self {
+        self.args.push(arg.into());
+        self
+    }
+
+    /// Add several server args.
+    pub fn args<I, S>(mut self, args: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.args.extend(args.into_iter().map(Into::into));
+        self
+    }
+
+    /// Title for backends that support one, unless the server block or args set one.
+    pub fn default_title<S: Into<String>>(mut self, title: S) -> Self {
+        self.default_title = Some(title.into());
+        self
+    }
+
+    /// File name for backends that keep one, unless the server block or args set one.
+    pub fn default_file_name<S: Into<String>>(mut self, name: S) -> Self {
+        self.default_file_name = Some(name.into());
+        self
+    }
+
+    /// Syntax for backends that support one, unless the server block or args set one.
+    pub fn default_syntax<S: Into<String>>(mut self, syntax: S) -> Self {
+        self.default_syntax = Some(syntax.into());
+        self
+    }
+
+    /// Whether to guess the syntax from file names and content, when the server block or args
+    /// don't set one. Default is the `detect_syntax` main config setting, or true.
+    pub fn detect_syntax(mut self, detect: bool) -> Self {
+        self.detect_syntax = detect;
+        self
+    }
+
+    /// Title and name the paste after a file name, and guess its syntax from the file extension.
+    pub fn file_defaults<P: AsRef<Path>>(self, path: P) -> Self {
+        let path = path.as_ref();
+        let paster = match path.file_name().and_then(|n| n.to_str()) {
+            Some(name) => self.default_title(name).default_file_name(name),
+            None => self,
+        };
+        if !paster.detect_syntax {
+            return paster;
+        }
+        match path
+            .extension()
+            .and_then(|e| e.to_str())
+            .and_then(syntax::from_extension)
+        {
+            Some(syntax) => paster.default_syntax(syntax),
+            None => paster,
+        }
+    }
+
+    /// Guess the syntax from the start of the content (see `syntax::DETECT_LEN`). A modeline or
+    /// `#!` line wins over the file extension; other guesses are only used if there is no
+    /// default syntax yet.
+    pub fn content_defaults(self, head: &[u8]) -> Self {
+        if !self.detect_syntax {
+            return self;
+        }
+        if let Some(syntax) = syntax::from_declaration(head) {
+            return self.default_syntax(syntax);
+        }
+        match syntax::from_content(head) {
+            Some(syntax) if self.default_syntax.is_none() => self.default_syntax(syntax),
+            _ => self,
+        }
+    }
+
+    /// Name of the server block that will be used.
+    pub fn server_name(&self) -> PasteResult<String> {
+        match self.server {
+            Some(ref server) => Ok(server.to_owned()),
+            None => self.config.default_server(),
+        }
+    }
+
+    /// Set up the chosen server, ready to paste to.
+    pub fn build(&self) -> PasteResult<Server> {
+        let name = self.server_name()?;
+        let server_config = self.config.server_config(&name)?;
+
+        let mut backend = server_config.backend.clone().extract_backend();
+        let mut args = Vec::with_capacity(self.args.len() + 1);
+        args.push(name.clone());
+        args.extend(self.args.iter().cloned());
+        backend.apply_args(args)?;
+
+        let backend_name = server_config.backend.name();
+        let encrypt = self.config.encrypt(&server_config);
+        if encrypt && backend_name == privatebin::NAME {
+            return Err(format!(
+                "server {} uses the privatebin backend, which encrypts pastes itself; \
+                 encrypt can't be used with it",
+                name
+            )
+            .into());
+        }
+
+        // file names and guessed syntaxes would give away what an encrypted paste holds
+        if !encrypt {
+            if let Some(ref title) = self.default_title {
+                backend.default_title(title);
+            }
+            if let Some(ref name) = self.default_file_name {
+                backend.default_file_name(name);
+            }
+        }
+        let warnings = self.map_syntax(backend_name, &mut *backend, !encrypt)?;
+
+        Ok(Server {
+            backend_name,
+            client_config: self.config.client_config(&server_config),
+            retry_policy: self.config.retry_policy(&server_config),
+            encrypt,
+            name,
+            backend,
+            warnings,
+        })
+    }
+
+    /// Translate the syntax set by the config or args to the backend's name for it, refusing
+    /// languages the backend has no name for. Names pc doesn't know are passed on as they are,
+    /// with a warning. Guessed default syntaxes are only used if the backend knows them, and
+    /// `use_default` is set.
+    fn map_syntax(
+        &self,
+        backend_name: &str,
+        backend: &mut dyn PasteClient,
+        use_default: bool,
+    ) -> PasteResult<Vec<String>> {
+        let table = match BACKENDS_SYNTAXES.get(backend_name) {
+            Some(table) => *table,
+            None => return Ok(vec![]),
+        };
+        let aliases = &self.config.syntax_aliases;
+
+        let given = match backend.syntax() {
+            // plain text, and no guessing
+            Some(syntax::NONE) => {
+                backend.set_syntax(None);
+                return Ok(vec![]);
+            }
+            Some(given) => given.to_owned(),
+            None if !use_default => return Ok(vec![]),
+            None => {
+                if let Some(ref default) = self.default_syntax {
+                    if let Some(language) = syntax::canonical(default, aliases)? {
+                        if let Some(lexer) = syntax::lexer(table, language) {
+                            backend.default_syntax(lexer);
+                        }
+                    }
+                }
+                return Ok(vec![]);
+            }
+        };
+
+        match syntax::canonical(&given, aliases)? {
+            Some(language) => match syntax::lexer(table, language) {
+                Some(lexer) => backend.set_syntax(Some(lexer)),
+                None => {
+                    return Err(format!(
+                        "the {} backend has no syntax for {}. Languages it supports: {}",
+                        backend_name,
+                        language,
+                        table
+                            .iter()
+                            .map(|(language, _)| *language)
+                            .collect::<Vec<_>>()
+                            .join(", ")
+                    )
+                    .into());
+                }
+            },
+            None if table.iter().any(|(_, lexer)| *lexer == given) => {}
+            None => {
+                return Ok(vec![format!(
+                    "unknown syntax {:?}, sending it to the {} backend as is",
+                    given, backend_name
+                )]);
+            }
+        }
+        Ok(vec![])
+    }
+
+    /// Set up the chosen server and paste `data` to it, guessing its syntax from the content if
+    /// there is none.
+    pub fn paste(&self, data: PasteData) -> PasteResult<PasteInfo> {
+        if !self.detect_syntax {
+            return self.build()?.paste(data);
+        }
+        let (head, data) = data.peek(syntax::DETECT_LEN)?;
+        self.clone().content_defaults(&head).build()?.paste(data)
+    }
+}
+
+/// A configured server, set up and ready to go.
+pub struct Server {
+    /// Name of the server block.
+    pub name: String,
+    /// Name of the backend the server uses.
+    pub backend_name: &'static str,
+    pub backend: Box<dyn PasteClient>,
+    pub client_config: ClientConfig,
+    pub retry_policy: RetryPolicy,
+    /// Whether pastes are encrypted before they are sent (see `crypto`).
+    pub encrypt: bool,
+    /// Problems with the settings that didn't stop the server being set up, eg. an unknown syntax.
+    pub warnings: Vec<String>,
+}
+
+impl Server {
+    /// Paste `data`, retrying transient failures. Files are opened again for each attempt, but
+    /// other readers such as stdin can only be read once, so are read into memory first if they
+    /// might need to be sent more than once. With the default retry policy they always are; set
+    /// `retry_policy.retries` to 0 to stream them.
+    pub fn paste(&self, data: PasteData) -> PasteResult<PasteInfo> {
+        if self.retry_policy.retries == 0 && !self.encrypt {
+            return self.backend.paste(&self.client_config, data);
+        }
+        let data = match data {
+            PasteData::Reader(_, _) => PasteData::from(data.into_bytes()?),
+            data => data,
+        };
+        self.paste_with(|| Ok(data.reopen().expect("bytes and files can be reopened")?))
+    }
+
+    /// Paste the data returned by `open`, which is called again for each retry. Encrypted pastes
+    /// are read once, and the same ciphertext is sent on each retry.
+    pub fn paste_with<F>(&self, mut open: F) -> PasteResult<PasteInfo>
+    where
+        F: FnMut() -> PasteResult<PasteData>,
+    {
+        if !self.encrypt {
+            return self
+                .retry_policy
+                .run(|| self.backend.paste(&self.client_config, open()?));
+        }
+
+        let (ciphertext, key) = crypto::encrypt(&open()?.into_bytes()?)?;
+        let mut info = self.retry_policy.run(|| {
+            self.backend.paste(
+                &self.client_config,
+                PasteData::from(ciphertext.clone().into_bytes()),
+            )
+        })?;
+        crypto::add_key(&mut info.url, &key)?;
+        if let Some(ref mut raw_url) = info.raw_url {
+            crypto::add_key(raw_url, &key)?;
+        }
+        Ok(info)
+    }
+
+    /// Paste several named files together as one paste, retrying transient failures. Only for
+    /// backends that support it, and not when encrypting.
+    pub fn paste_files(&self, files: &[(String, Vec<u8>)]) -> PasteResult<PasteInfo> {
+        if self.encrypt {
+            return Err("encrypted pastes can't hold several files"
+                .to_owned()
+                .into());
+        }
+        self.retry_policy.run(|| {
+            let files = files
+                .iter()
+                .map(|(name, data)| (name.to_owned(), PasteData::from(data.clone())))
+                .collect();
+            self.backend.paste_files(&self.client_config, files)
+        })
+    }
+
+    /// Fetch the raw content of an existing paste, retrying transient failures.
+    pub fn fetch(&self, url: &Url) -> PasteResult<Vec<u8>> {
+        self.retry_policy
+            .run(|| self.backend.fetch(&self.client_config, url))
+    }
+
+    /// Delete an existing paste, retrying transient failures.
+    pub fn delete(&self, url: &Url, token: Option<&str>) -> PasteResult<()> {
+        self.retry_policy
+            .run(|| self.backend.delete(&self.client_config, url, token))
+    }
+}
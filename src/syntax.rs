@@ -0,0 +1,412 @@
+//! Backend independent syntax names.
+//!
+//! Syntax can be given as one of the canonical `LANGUAGES`, a common alias or file extension for
+//! one, or an alias from the `[syntax_aliases]` config section. Each backend that supports syntax
+//! highlighting maps the canonical names to its own lexer names with a `SYNTAXES` table.
+
+use std::collections::HashMap;
+
+use crate::error::PasteResult;
+
+/// Canonical names of the languages pc knows about.
+pub const LANGUAGES: [&str; 31] = [
+    "bash",
+    "c",
+    "cpp",
+    "csharp",
+    "css",
+    "diff",
+    "go",
+    "haskell",
+    "html",
+    "ini",
+    "java",
+    "javascript",
+    "json",
+    "kotlin",
+    "latex",
+    "lua",
+    "markdown",
+    "perl",
+    "php",
+    "python",
+    "ruby",
+    "rust",
+    "scala",
+    "sql",
+    "swift",
+    "text",
+    "toml",
+    "typescript",
+    "vim",
+    "xml",
+    "yaml",
+];
+
+/// Syntax asking for plain text, rather than leaving the syntax to be guessed.
+pub const NONE: &str = "NONE";
+
+/// The syntax a backend sends to its server: nothing for plain text, as asked for with `NONE`.
+pub fn to_send(syntax: &Option<String>) -> Option<&str> {
+    syntax.as_deref().filter(|syntax| *syntax != NONE)
+}
+
+/// Lexer names for backends that use pygments, which is most of them.
+pub const PYGMENTS: &[(&str, &str)] = &[
+    ("bash", "bash"),
+    ("c", "c"),
+    ("cpp", "cpp"),
+    ("csharp", "csharp"),
+    ("css", "css"),
+    ("diff", "diff"),
+    ("go", "go"),
+    ("haskell", "haskell"),
+    ("html", "html"),
+    ("ini", "ini"),
+    ("java", "java"),
+    ("javascript", "js"),
+    ("json", "json"),
+    ("kotlin", "kotlin"),
+    ("latex", "tex"),
+    ("lua", "lua"),
+    ("markdown", "md"),
+    ("perl", "perl"),
+    ("php", "php"),
+    ("python", "python"),
+    ("ruby", "ruby"),
+    ("rust", "rust"),
+    ("scala", "scala"),
+    ("sql", "sql"),
+    ("swift", "swift"),
+    ("text", "text"),
+    ("toml", "toml"),
+    ("typescript", "ts"),
+    ("vim", "vim"),
+    ("xml", "xml"),
+    ("yaml", "yaml"),
+];
+
+/// Common names for languages, besides their file extensions.
+fn builtin_alias(name: &str) -> Option<&'static str> {
+    let language = match name {
+        "sh" | "shell" | "zsh" => "bash",
+        "c++" => "cpp",
+        "c#" => "csharp",
+        "golang" => "go",
+        "js" | "node" => "javascript",
+        "tex" => "latex",
+        "md" => "markdown",
+        "py" | "py3" | "python3" => "python",
+        "plain" | "plaintext" | "txt" => "text",
+        "ts" => "typescript",
+        _ => return None,
+    };
+    Some(language)
+}
+
+/// Guess a language from a file extension.
+pub fn from_extension(extension: &str) -> Option<&'static str> {
+    let language = match extension.to_lowercase().as_str() {
+        "c" | "h" => "c",
+        "cc" | "cpp" | "cxx" | "hpp" | "hh" => "cpp",
+        "cs" => "csharp",
+        "css" => "css",
+        "diff" | "patch" => "diff",
+        "go" => "go",
+        "hs" => "haskell",
+        "htm" | "html" => "html",
+        "ini" | "cfg" => "ini",
+        "java" => "java",
+        "js" => "javascript",
+        "json" => "json",
+        "kt" => "kotlin",
+        "lua" => "lua",
+        "md" | "markdown" => "markdown",
+        "php" => "php",
+        "pl" | "pm" => "perl",
+        "py" => "python",
+        "rb" => "ruby",
+        "rs" => "rust",
+        "scala" => "scala",
+        "sh" | "bash" => "bash",
+        "sql" => "sql",
+        "swift" => "swift",
+        "tex" => "latex",
+        "toml" => "toml",
+        "ts" => "typescript",
+        "txt" => "text",
+        "vim" => "vim",
+        "xml" => "xml",
+        "yaml" | "yml" => "yaml",
+        _ => return None,
+    };
+    Some(language)
+}
+
+/// The canonical language for a syntax name, or None if pc doesn't know it. Aliases from the
+/// config win over the built in ones, and must name a canonical language.
+pub fn canonical(
+    name: &str,
+    aliases: &HashMap<String, String>,
+) -> PasteResult<Option<&'static str>> {
+    let name = name.to_lowercase();
+    if let Some(target) = aliases.get(&name) {
+        return match LANGUAGES.iter().find(|l| **l == target.as_str()) {
+            Some(language) => Ok(Some(language)),
+            None => Err(format!(
+                "syntax alias {} = {:?} is not one of the known languages: {}",
+                name,
+                target,
+                LANGUAGES.join(", ")
+            )
+            .into()),
+        };
+    }
+    if let Some(language) = LANGUAGES.iter().find(|l| **l == name) {
+        return Ok(Some(language));
+    }
+    Ok(builtin_alias(&name).or_else(|| from_extension(&name)))
+}
+
+/// Look up a backend's lexer name for a canonical language.
+pub fn lexer(table: &[(&str, &'static str)], language: &str) -> Option<&'static str> {
+    table
+        .iter()
+        .find(|(lang, _)| *lang == language)
+        .map(|(_, lexer)| *lexer)
+}
+
+/// How much of the start of the content is looked at to detect its language.
+pub const DETECT_LEN: usize = 8192;
+
+/// The language the content declares itself to be, with a vim or emacs modeline in its first or
+/// last few lines, or a `#!` line.
+pub fn from_declaration(content: &[u8]) -> Option<&'static str> {
+    let content = String::from_utf8_lossy(content);
+    let lines: Vec<&str> = content.lines().collect();
+    let edges = lines
+        .iter()
+        .take(5)
+        .chain(lines.iter().skip(5.max(lines.len().saturating_sub(5))));
+    for line in edges {
+        if let Some(language) = from_modeline(line) {
+            return Some(language);
+        }
+    }
+    lines.first().and_then(|line| from_shebang(line))
+}
+
+/// Language from a vim (`vim: set ft=python:`) or emacs (`-*- mode: python -*-`) modeline.
+fn from_modeline(line: &str) -> Option<&'static str> {
+    if let Some(start) = line.find("-*-") {
+        let rest = &line[start + 3..];
+        let settings = &rest[..rest.find("-*-")?];
+        for setting in settings.split(';') {
+            let setting = setting.trim();
+            let name = match setting.find(':') {
+                Some(i) if setting[..i].trim().eq_ignore_ascii_case("mode") => &setting[i + 1..],
+                Some(_) => continue,
+                None => setting,
+            };
+            return canonical(name.trim(), &HashMap::new()).ok()?;
+        }
+        return None;
+    }
+
+    let start = ["vim:", "vi:", "ex:"]
+        .iter()
+        .filter_map(|marker| line.find(marker).map(|i| i + marker.len()))
+        .min()?;
+    line[start..]
+        .split(|c: char| c == ':' || c.is_whitespace())
+        .filter_map(|option| {
+            let mut parts = option.splitn(2, '=');
+            match (parts.next(), parts.next()) {
+                (Some("ft"), Some(value))
+                | (Some("filetype"), Some(value))
+                | (Some("syn"), Some(value))
+                | (Some("syntax"), Some(value)) => Some(value),
+                _ => None,
+            }
+        })
+        .filter_map(|value| canonical(value, &HashMap::new()).ok()?)
+        .next()
+}
+
+/// Language from the interpreter in a `#!` line, eg. `#!/usr/bin/env python3`.
+fn from_shebang(line: &str) -> Option<&'static str> {
+    if !line.starts_with("#!") {
+        return None;
+    }
+    let mut words = line[2..].split_whitespace();
+    let mut interpreter = words.next()?.rsplit('/').next()?;
+    if interpreter == "env" {
+        interpreter = words.find(|word| !word.starts_with('-') && !word.contains('='))?;
+    }
+    let interpreter = interpreter.to_lowercase();
+    let unversioned = interpreter.trim_end_matches(|c: char| c.is_ascii_digit() || c == '.');
+    let language = match unversioned {
+        "sh" | "bash" | "dash" | "ksh" | "zsh" => "bash",
+        "nodejs" => "javascript",
+        _ => return canonical(unversioned, &HashMap::new()).ok()?,
+    };
+    Some(language)
+}
+
+/// Guess the language from what the content looks like: diffs, json, xml and html, and stack
+/// traces from a few languages.
+pub fn from_content(content: &[u8]) -> Option<&'static str> {
+    let content = String::from_utf8_lossy(content);
+    let trimmed = content.trim();
+
+    let mut lines = content.lines().peekable();
+    while let Some(line) = lines.next() {
+        if line.starts_with("diff --git ") || line.starts_with("@@ -") {
+            return Some("diff");
+        }
+        if line.starts_with("--- ") && lines.peek().filter(|l| l.starts_with("+++ ")).is_some() {
+            return Some("diff");
+        }
+    }
+
+    if content.contains("Traceback (most recent call last):") {
+        return Some("python");
+    }
+    if content.contains("' panicked at ") {
+        return Some("rust");
+    }
+    let java_frames = content
+        .lines()
+        .filter(|line| line.trim_start().starts_with("at ") && line.trim_end().ends_with(')'))
+        .count();
+    if java_frames >= 2 && content.contains("Exception") {
+        return Some("java");
+    }
+
+    if (trimmed.starts_with('{') || trimmed.starts_with('['))
+        && serde_json::from_str::<serde_json::Value>(trimmed).is_ok()
+    {
+        return Some("json");
+    }
+    let lowercase = trimmed.to_lowercase();
+    if lowercase.starts_with("<!doctype html") || lowercase.starts_with("<html") {
+        return Some("html");
+    }
+    if trimmed.starts_with("<?xml") || (trimmed.starts_with('<') && trimmed.ends_with('>')) {
+        return Some("xml");
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn shebangs() {
+        assert_eq!(from_shebang("#!/bin/sh"), Some("bash"));
+        assert_eq!(from_shebang("#!/usr/bin/env bash"), Some("bash"));
+        assert_eq!(from_shebang("#!/usr/bin/python3.11"), Some("python"));
+        assert_eq!(from_shebang("#!/usr/bin/env -S python3 -u"), Some("python"));
+        assert_eq!(
+            from_shebang("#!/usr/bin/env -S NODE_ENV=production nodejs"),
+            Some("javascript")
+        );
+        assert_eq!(from_shebang("#! /usr/bin/perl -w"), Some("perl"));
+        assert_eq!(from_shebang("#!/usr/bin/env"), None);
+        assert_eq!(from_shebang("#!/usr/bin/frobnicate"), None);
+        assert_eq!(from_shebang("# /bin/sh"), None);
+    }
+
+    #[test]
+    fn vim_modelines() {
+        assert_eq!(from_modeline("# vim: set ft=python:"), Some("python"));
+        assert_eq!(from_modeline("// vim: ts=4 sw=4 ft=rust"), Some("rust"));
+        assert_eq!(from_modeline("/* vi: syntax=c */"), Some("c"));
+        assert_eq!(from_modeline("\" ex: set filetype=vim :"), Some("vim"));
+        assert_eq!(from_modeline("# vim: set ft=yml:"), Some("yaml"));
+        assert_eq!(from_modeline("# vim: set ts=4 sw=4:"), None);
+        assert_eq!(from_modeline("# vim: set ft=frobnicate:"), None);
+    }
+
+    #[test]
+    fn emacs_modelines() {
+        assert_eq!(from_modeline("# -*- mode: python -*-"), Some("python"));
+        assert_eq!(from_modeline(";; -*- Mode: Lisp; -*-"), None);
+        assert_eq!(
+            from_modeline("# -*- coding: utf-8; mode: ruby -*-"),
+            Some("ruby")
+        );
+        assert_eq!(from_modeline("/* -*- c++ -*- */"), Some("cpp"));
+        assert_eq!(from_modeline("# -*- coding: utf-8 -*-"), None);
+        assert_eq!(from_modeline("# -*- mode: python"), None);
+    }
+
+    #[test]
+    fn declarations() {
+        assert_eq!(
+            from_declaration(b"#!/usr/bin/env -S python3 -u\nprint('hi')\n"),
+            Some("python")
+        );
+        // modelines win over the interpreter
+        assert_eq!(
+            from_declaration(b"#!/bin/sh\n# vim: set ft=perl:\nexec perl -x\n"),
+            Some("perl")
+        );
+
+        let mut content = "let x = 1;\n".repeat(20);
+        content.push_str("// vim: set ft=rust:\n");
+        assert_eq!(from_declaration(content.as_bytes()), Some("rust"));
+
+        // modelines are only looked for in the first and last few lines
+        let content = format!(
+            "{}// vim: set ft=rust:\n{}",
+            "x\n".repeat(10),
+            "x\n".repeat(10)
+        );
+        assert_eq!(from_declaration(content.as_bytes()), None);
+        // and shebangs only in the first
+        assert_eq!(from_declaration(b"\n#!/bin/sh\n"), None);
+        assert_eq!(from_declaration(b""), None);
+    }
+
+    #[test]
+    fn content() {
+        assert_eq!(
+            from_content(b"diff --git a/src/main.rs b/src/main.rs\nindex 1..2\n"),
+            Some("diff")
+        );
+        assert_eq!(
+            from_content(b"--- a/file\n+++ b/file\n@@ -1 +1 @@\n-a\n+b\n"),
+            Some("diff")
+        );
+        assert_eq!(
+            from_content(
+                b"Traceback (most recent call last):\n  File \"x.py\", line 1, in <module>\n"
+            ),
+            Some("python")
+        );
+        assert_eq!(
+            from_content(b"thread 'main' panicked at src/main.rs:1:5:\nexplicit panic\n"),
+            Some("rust")
+        );
+        assert_eq!(
+            from_content(
+                b"java.lang.IllegalStateException: no\n\tat a.B.c(B.java:1)\n\tat a.B.d(B.java:2)\n"
+            ),
+            Some("java")
+        );
+        assert_eq!(from_content(b"  {\"a\": [1, 2]}\n"), Some("json"));
+        assert_eq!(from_content(b"[1, 2"), None);
+        assert_eq!(
+            from_content(b"<!DOCTYPE html>\n<html></html>\n"),
+            Some("html")
+        );
+        assert_eq!(
+            from_content(b"<?xml version=\"1.0\"?>\n<a/>\n"),
+            Some("xml")
+        );
+        assert_eq!(from_content(b"<a><b/></a>"), Some("xml"));
+        assert_eq!(from_content(b"just some text\n--- not a diff\n"), None);
+    }
+}
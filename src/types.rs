@@ -1,10 +1,206 @@
+use std::collections::BTreeMap;
 use std::fmt::Display;
+use std::fs::File;
+use std::io::{self, Cursor, Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
 
+use reqwest::Body;
 use url::Url;
 
+use crate::client::ClientConfig;
 use crate::error::PasteResult;
 
+/// Content to be pasted. Input from stdin or a file is kept as a reader so that backends able to
+/// stream uploads don't need to hold it all in memory; other backends buffer it with
+/// `into_bytes`.
+pub enum PasteData {
+    Bytes(Vec<u8>),
+    /// An open file, along with its path, so it can be opened again if the content needs to be
+    /// sent more than once, and its size.
+    File(PathBuf, File, u64),
+    /// Reader for the content, along with its size if known in advance.
+    Reader(Box<dyn Read + Send>, Option<u64>),
+}
+
+impl PasteData {
+    pub fn stdin() -> Self {
+        PasteData::Reader(Box::new(io::stdin()), None)
+    }
+
+    pub fn file<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        let file = File::open(path.as_ref())?;
+        let size = file.metadata()?.len();
+        Ok(PasteData::File(path.as_ref().to_owned(), file, size))
+    }
+
+    /// A fresh copy of the content, to send it again: bytes are copied, and files opened again.
+    /// None for other readers, which can only be read once.
+    pub fn reopen(&self) -> Option<io::Result<Self>> {
+        match self {
+            PasteData::Bytes(bytes) => Some(Ok(PasteData::Bytes(bytes.clone()))),
+            PasteData::File(path, _, _) => Some(PasteData::file(path)),
+            PasteData::Reader(_, _) => None,
+        }
+    }
+
+    /// Read all the content into memory.
+    pub fn into_bytes(self) -> io::Result<Vec<u8>> {
+        match self {
+            PasteData::Bytes(bytes) => Ok(bytes),
+            PasteData::File(_, mut file, size) => {
+                let mut buffer = Vec::with_capacity(size as usize);
+                file.read_to_end(&mut buffer)?;
+                Ok(buffer)
+            }
+            PasteData::Reader(mut reader, size) => {
+                let mut buffer = Vec::with_capacity(size.unwrap_or(0) as usize);
+                reader.read_to_end(&mut buffer)?;
+                Ok(buffer)
+            }
+        }
+    }
+
+    /// Read up to `len` bytes from the start of the content, without losing them for the paste.
+    pub fn peek(self, len: usize) -> io::Result<(Vec<u8>, Self)> {
+        match self {
+            PasteData::Bytes(bytes) => Ok((bytes[..len.min(bytes.len())].to_vec(), bytes.into())),
+            // files are rewound rather than wrapped, so they can still be opened again
+            PasteData::File(path, mut file, size) => {
+                let mut head = Vec::with_capacity(len);
+                (&mut file).take(len as u64).read_to_end(&mut head)?;
+                file.seek(SeekFrom::Start(0))?;
+                Ok((head, PasteData::File(path, file, size)))
+            }
+            PasteData::Reader(reader, size) => {
+                let mut head = Vec::with_capacity(len);
+                let mut reader = reader.take(len as u64);
+                reader.read_to_end(&mut head)?;
+                let reader = Cursor::new(head.clone()).chain(reader.into_inner());
+                Ok((head, PasteData::Reader(Box::new(reader), size)))
+            }
+        }
+    }
+
+    pub fn into_reader(self) -> Box<dyn Read + Send> {
+        match self {
+            PasteData::Bytes(bytes) => Box::new(Cursor::new(bytes)),
+            PasteData::File(_, file, _) => Box::new(file),
+            PasteData::Reader(reader, _) => reader,
+        }
+    }
+
+    /// Request body that streams the content as it is read.
+    pub fn into_body(self) -> Body {
+        match self {
+            PasteData::Bytes(bytes) => Body::from(bytes),
+            PasteData::File(_, file, size) => Body::sized(file, size),
+            PasteData::Reader(reader, Some(size)) => Body::sized(reader, size),
+            PasteData::Reader(reader, None) => Body::new(reader),
+        }
+    }
+}
+
+impl From<Vec<u8>> for PasteData {
+    fn from(bytes: Vec<u8>) -> Self {
+        PasteData::Bytes(bytes)
+    }
+}
+
+/// Result of a successful paste. Backends fill in as much as their server reports.
+#[derive(Debug, Clone)]
+pub struct PasteInfo {
+    /// Url to view the paste.
+    pub url: Url,
+    /// Url of the raw content of the paste, if the server has one. Can be the same as `url`.
+    pub raw_url: Option<Url>,
+    /// Token returned by the server that is needed to delete the paste later, if any.
+    pub delete_token: Option<String>,
+    /// Url to manage (eg. view status of, or delete) the paste, if any.
+    pub manage_url: Option<Url>,
+    /// When the paste expires, if it does.
+    pub expires: Option<SystemTime>,
+    /// Whether a password is needed to view the paste.
+    pub password_required: bool,
+    /// Anything else the server reported, by name.
+    pub extras: BTreeMap<String, String>,
+}
+
+impl From<Url> for PasteInfo {
+    fn from(url: Url) -> Self {
+        PasteInfo {
+            url,
+            raw_url: None,
+            delete_token: None,
+            manage_url: None,
+            expires: None,
+            password_required: false,
+            extras: BTreeMap::new(),
+        }
+    }
+}
+
 pub trait PasteClient: Display {
     fn apply_args(&mut self, args: Vec<String>) -> clap::Result<()>;
-    fn paste(&self, data: String) -> PasteResult<Url>;
+    fn paste(&self, client_config: &ClientConfig, data: PasteData) -> PasteResult<PasteInfo>;
+
+    /// Paste several named files together as one paste, for backends that support it (see
+    /// `Capabilities::multi_file`).
+    fn paste_files(
+        &self,
+        _client_config: &ClientConfig,
+        _files: Vec<(String, PasteData)>,
+    ) -> PasteResult<PasteInfo> {
+        Err(format!(
+            "pasting several files as one paste is not supported by backend: {}",
+            self
+        )
+        .into())
+    }
+
+    /// Use `title` as the paste title, unless one was already set by the config or args. Does
+    /// nothing for backends that don't support titles.
+    fn default_title(&mut self, _title: &str) {}
+
+    /// Use `syntax` for syntax highlighting, unless one was already set by the config or args.
+    /// Does nothing for backends that don't support syntax highlighting.
+    fn default_syntax(&mut self, _syntax: &str) {}
+
+    /// Use `name` as the file name for the content, unless one was already set by the config or
+    /// args. Does nothing for backends that don't keep file names.
+    fn default_file_name(&mut self, _name: &str) {}
+
+    /// Title the paste will be given, for backends that support titles.
+    fn title(&self) -> Option<&str> {
+        None
+    }
+
+    /// Replace the syntax set by the config or args, eg. with the backend's name for it. None
+    /// means plain text.
+    fn set_syntax(&mut self, _syntax: Option<&str>) {}
+
+    /// Syntax the paste will be highlighted with, for backends that support syntax highlighting.
+    fn syntax(&self) -> Option<&str> {
+        None
+    }
+
+    /// Host that pastes created by this server are served from. Used to find the server block
+    /// responsible for an existing paste url.
+    fn host(&self) -> Option<String>;
+
+    /// Retrieve the raw content of an existing paste.
+    fn fetch(&self, _client_config: &ClientConfig, _url: &Url) -> PasteResult<Vec<u8>> {
+        Err(format!("fetching pastes is not supported by backend: {}", self).into())
+    }
+
+    /// Delete an existing paste. `token` is the delete token returned when the paste was created,
+    /// for backends that need one.
+    fn delete(
+        &self,
+        _client_config: &ClientConfig,
+        _url: &Url,
+        _token: Option<&str>,
+    ) -> PasteResult<()> {
+        Err(format!("deleting pastes is not supported by backend: {}", self).into())
+    }
 }
@@ -1,10 +1,13 @@
-use std::error::Error;
-use std::fs::{File, OpenOptions};
-use std::io::{self, Read, Write};
-use std::time::Duration;
+use std::collections::HashMap;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::path::Path;
+use std::time::{Duration, SystemTime};
 
 use url::Url;
 
+use crate::error::PasteResult;
+
 pub mod serde_url {
     use serde::Deserialize;
     use serde::{Deserializer, Serializer};
@@ -40,19 +43,104 @@ pub fn read_file(fname: &str) -> io::Result<String> {
     Ok(contents)
 }
 
-pub fn read_stdin() -> io::Result<String> {
-    let mut buffer = String::new();
-    let stdin = io::stdin();
-    let mut handle = stdin.lock();
+/// For backends whose api can only carry text; gives a helpful error for binary input.
+pub fn bytes_to_text(data: Vec<u8>, backend: &str) -> PasteResult<String> {
+    String::from_utf8(data).map_err(|e| {
+        format!(
+            "the {} backend only supports text, but the input is not valid utf-8 ({})",
+            backend,
+            e.utf8_error()
+        )
+        .into()
+    })
+}
 
-    handle.read_to_string(&mut buffer)?;
+/// Parses an http date (eg. "Wed, 21 Oct 2015 07:28:00 GMT"), as used in Expires headers.
+pub fn parse_http_date(date: &str) -> Option<SystemTime> {
+    const MONTHS: [&str; 12] = [
+        "Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec",
+    ];
 
-    Ok(buffer)
+    let parts: Vec<&str> = date.split_whitespace().collect();
+    if parts.len() != 6 || parts[5] != "GMT" {
+        return None;
+    }
+    let month = MONTHS.iter().position(|m| *m == parts[2])? + 1;
+    humantime::parse_rfc3339(&format!(
+        "{}-{:02}-{:0>2}T{}Z",
+        parts[3], month, parts[1], parts[4]
+    ))
+    .ok()
+}
+
+/// Opens a file for appending, creating it readable only by the user if it doesn't exist. Used for
+/// the histfile and tokenfile, which hold delete tokens.
+pub fn open_private_append(path: &str) -> io::Result<File> {
+    let mut options = OpenOptions::new();
+    options.append(true).create(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    options.open(path)
 }
 
-pub fn write_hist(paste_url: Url, path: &str) -> Result<(), Box<dyn Error>> {
-    let mut file = OpenOptions::new().append(true).create(true).open(path)?;
-    file.write_all(format!("{}\n", paste_url).as_bytes())?;
+/// Records the delete token for a paste url, as a `<url> <token>` line.
+pub fn write_token(paste_url: &Url, token: &str, path: &str) -> PasteResult<()> {
+    let mut file = open_private_append(path)?;
+    file.write_all(format!("{} {}\n", paste_url, token).as_bytes())?;
+    Ok(())
+}
+
+/// Looks up the delete token recorded for a paste url. The most recent entry wins.
+pub fn read_token(paste_url: &Url, path: &str) -> PasteResult<Option<String>> {
+    let file = BufReader::new(File::open(path)?);
+    let mut token = None;
+    for line in file.lines() {
+        let line = line?;
+        let mut parts = line.split_whitespace();
+        if let (Some(url), Some(t)) = (parts.next(), parts.next()) {
+            if url == paste_url.as_str() {
+                token = Some(t.to_owned());
+            }
+        }
+    }
+    Ok(token)
+}
+
+/// Reads the times that servers last failed, from `<server> <unix timestamp>` lines.
+pub fn read_failures(path: &Path) -> PasteResult<HashMap<String, SystemTime>> {
+    let mut failures = HashMap::new();
+    if !path.exists() {
+        return Ok(failures);
+    }
+
+    let file = BufReader::new(File::open(path)?);
+    for line in file.lines() {
+        let line = line?;
+        let mut parts = line.split_whitespace();
+        if let (Some(server), Some(timestamp)) = (parts.next(), parts.next()) {
+            let failed_at = SystemTime::UNIX_EPOCH + Duration::from_secs(timestamp.parse()?);
+            failures.insert(server.to_owned(), failed_at);
+        }
+    }
+    Ok(failures)
+}
+
+pub fn write_failures(failures: &HashMap<String, SystemTime>, path: &Path) -> PasteResult<()> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+
+    let mut file = File::create(path)?;
+    for (server, failed_at) in failures {
+        let timestamp = failed_at
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .map_err(|e| e.to_string())?
+            .as_secs();
+        file.write_all(format!("{} {}\n", server, timestamp).as_bytes())?;
+    }
     Ok(())
 }
 
@@ -68,6 +156,14 @@ pub fn override_option_with_option_none(old: &mut Option<String>, new: Option<St
     }
 }
 
+/// when the current value is a syntax and needs to be optionally overridden with a string. An
+/// explicit "NONE" is kept rather than clearing it, so that plain text can be told apart from no
+/// syntax being given, which leaves it to be guessed; backends send nothing for it (see
+/// `syntax::to_send`).
+pub fn override_syntax(old: &mut Option<String>, new: Option<String>) {
+    override_if_present(old, new.map(Some));
+}
+
 /// when the current value is an optional duration and needs to be optionally overridden with a
 /// string, or forced to None with an explicit "NONE".
 pub fn override_option_duration_with_option_none(
@@ -11,3 +11,5 @@ $BIN -c default_config.toml dump-config
 $BIN -c example_config.toml dump-config
 $BIN -c example_config.toml list
 $BIN -c example_config.toml list-backends
+$BIN -c example_config.toml list-backends --matrix
+$BIN -c example_config.toml list --supports delete